@@ -1,38 +1,492 @@
-use clap::Parser;
+use std::{
+    collections::HashMap,
+    io::{stdin, IsTerminal, Read},
+    path::PathBuf,
+};
 
-use crate::{client::state::Uninitialized, template::Templates};
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+
+use crate::{
+    client::{state::Uninitialized, TokenSource, SYSTEM_TIMEZONE},
+    config::Config,
+    output::{Format, Output, Structured},
+    template::{Locale, Preset, Templates},
+    xdg::config_dir,
+};
 
 /// The CLI arguments.
 #[derive(Parser)]
-#[clap(version, author, about)]
+#[clap(
+    version,
+    author,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Args {
-    /// Slack API token.
-    #[arg(long, env = "SLACK_TOKEN")]
-    pub token: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Slack API token, used when no workspace-specific token is configured. See the
+    /// documentation for the workspace-specific tokens.
+    #[arg(long, env = "SLACK_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Path to a file containing the Slack API token. Takes precedence over `--token`.
+    #[arg(long, env = "SLACK_TOKEN_FILE", value_name = "PATH")]
+    pub token_file: Option<PathBuf>,
+
+    /// Shell command which prints the Slack API token, e.g. `pass show slack`. Takes precedence
+    /// over `--token-file` and `--token`.
+    #[arg(long, env = "SLACK_TOKEN_COMMAND", value_name = "COMMAND")]
+    pub token_command: Option<String>,
+
+    /// Read the Slack API tokens from the OS secret service, i.e. Secret Service on Linux or
+    /// Keychain on macOS, with `sam` as the service and the workspace or `default` as the
+    /// account.
+    #[arg(long)]
+    pub token_keyring: bool,
+
+    /// Slack API tokens configured in the configuration file, keyed by the workspace.
+    #[arg(skip)]
+    pub workspace_tokens: HashMap<String, String>,
+
+    /// The time zones configured in the configuration file, keyed by the workspace. Empty when
+    /// `--timezone` is given.
+    #[arg(skip)]
+    pub workspace_timezones: HashMap<String, String>,
+
+    /// Path to a Slack workspace export, either a directory or a ZIP archive, to resolve the
+    /// messages from instead of the Slack API.
+    #[arg(long, value_name = "PATH")]
+    pub export: Option<PathBuf>,
 
     /// Include the message body as a quote.
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_quote")]
     pub quote: bool,
 
-    /// The IANA time zone database identifiers to use for the timestamp.
-    #[arg(short, long, default_value = "Asia/Tokyo")]
-    pub timezone: String,
+    /// Do not include the message body, even if `quote = true` in the configuration file.
+    #[arg(long, overrides_with = "quote")]
+    pub no_quote: bool,
+
+    /// Include the whole thread the message belongs to, i.e. the parent message and all the
+    /// replies, using the thread templates.
+    #[arg(long)]
+    pub thread: bool,
+
+    /// Show a line of the reactions and the number of the replies under the quote, e.g.
+    /// `✅ ×5 👀 ×2 · 💬 3 replies`.
+    #[arg(long, overrides_with = "no_reactions")]
+    pub reactions: bool,
+
+    /// Do not show the line of the reactions, even if `reactions = true` in the configuration
+    /// file.
+    #[arg(long, overrides_with = "reactions")]
+    pub no_reactions: bool,
+
+    /// Download the files shared with the messages to the given directory, and refer to the local
+    /// paths instead of the Slack URLs of them.
+    #[arg(long, value_name = "DIR")]
+    pub download_files: Option<PathBuf>,
+
+    /// The IANA time zone database identifiers to use for the timestamp, separated by commas or
+    /// given multiple times. The first one is used for the date variables, and all of them for
+    /// `zones`. Takes precedence over the ones in the configuration file. Defaults to the time
+    /// zone of the system, i.e. `TZ` or `/etc/localtime`.
+    #[arg(short, long, value_delimiter = ',', global = true)]
+    pub timezone: Vec<String>,
+
+    /// The locale of the names of the months and the weekdays, and the format of `timestamp`,
+    /// e.g. `ja_JP` or `de_DE`. Defaults to English names and the ISO 8601 style format. The
+    /// `relative` filter is always in English.
+    #[arg(long, env = "SAM_LOCALE", global = true)]
+    pub locale: Option<Locale>,
+
+    /// The preset of the plain text templates, i.e. the markup language to write. Templates given
+    /// explicitly take precedence. Defaults to `markdown`.
+    #[arg(long, value_enum, global = true)]
+    pub preset: Option<Preset>,
 
     #[command(flatten)]
     pub templates: Templates,
 
-    /// Slack message URL to process. Leave empty to use the clipboard.
+    /// Name of the template to render the messages with, either the built-in `link`, `quote`,
+    /// `quote_zones`, or `thread`, or the one in the templates directory. Implies fetching the
+    /// message body. Defaults to the built-in one according to `--quote` and `--thread`.
+    #[arg(long, value_name = "NAME")]
+    pub template: Option<String>,
+
+    /// Path to the directory of the named templates. Defaults to `templates` in the XDG config
+    /// directory, if it exists.
+    #[arg(long, env = "SAM_TEMPLATES_DIR", value_name = "PATH")]
+    pub templates_dir: Option<PathBuf>,
+
+    /// Do not use the on-disk cache of the users, channels, and messages at all.
+    #[arg(long, conflicts_with = "refresh")]
+    pub no_cache: bool,
+
+    /// Ignore the cached users, channels, and messages, but still update the cache.
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Path to the configuration file. Defaults to `config.toml` in the XDG config directory, if
+    /// it exists.
+    #[arg(long, env = "SAM_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Name of the profile in the configuration file to use.
+    #[arg(short, long, env = "SAM_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Where to write the result: `stdout`, `clipboard`, `file:<PATH>`, or `both` for the standard
+    /// output and the clipboard.
+    #[arg(short, long, value_name = "OUTPUT", default_value = "both")]
+    pub output: Output,
+
+    /// The format of the result to write.
+    #[arg(short, long, value_enum, default_value_t = Format::Both)]
+    pub format: Format,
+
+    /// Write the template contexts of the messages as JSON instead of the rendered texts, for
+    /// other programs to consume. Implies fetching the message body.
+    #[arg(long, conflicts_with_all = ["yaml", "extract"])]
+    pub json: bool,
+
+    /// Write the template contexts of the messages as YAML instead of the rendered texts. Implies
+    /// fetching the message body.
+    #[arg(long, conflicts_with = "extract")]
+    pub yaml: bool,
+
+    /// Append the messages rendered with the plain text quote template to the file at the given
+    /// path, which is a template itself, e.g. `~/notes/{{ iso_date }}.md`. Implies fetching the
+    /// message body.
+    #[arg(long, value_name = "PATH_TEMPLATE")]
+    pub append_to: Option<String>,
+
+    /// Path to a file containing Slack message URLs, one per line.
+    #[arg(long, value_name = "PATH")]
+    pub from_file: Option<PathBuf>,
+
+    /// Scan the input text for Slack message URLs and replace each of them with the rendered
+    /// message, preserving the surrounding text. The text is read from the file specified by
+    /// `--from-file`, the standard input when it is piped, or the clipboard.
+    #[arg(short = 'x', long, conflicts_with = "urls")]
+    pub extract: bool,
+
+    /// Slack message URLs to process. Leave empty to read them from the standard input when it is
+    /// piped, or from the clipboard otherwise.
     #[arg()]
-    pub url: Option<String>,
+    pub urls: Vec<String>,
+}
+
+/// The subcommands.
+#[derive(Subcommand)]
+pub enum Command {
+    /// Manage the on-disk cache.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// List all the template variables with the descriptions and the examples, using the time zone
+    /// in the environment variables and the configuration file.
+    Vars {
+        /// Slack message URL to take the examples from. Leave empty to use the current time and
+        /// the sample values.
+        url: Option<String>,
+
+        /// Write the variables as JSON, e.g. for editor completion plugins.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Work with the templates, using the template options in the environment variables and the
+    /// configuration file.
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+}
+
+/// The subcommands of `template`.
+#[derive(Subcommand)]
+pub enum TemplateCommand {
+    /// Compile all the configured templates, and report the errors and the variables which are
+    /// not available, without retrieving any message.
+    Check,
+
+    /// Render the template with the given name against a sample message, and print the plain text
+    /// and the rich text, without retrieving any message.
+    Preview {
+        /// Name of the template, either the built-in `link`, `quote`, `quote_zones`, or `thread`,
+        /// or the one in the templates directory.
+        name: String,
+
+        /// Path to a JSON file with the values of the template variables to use instead of the
+        /// sample ones. The output of `--json` is also accepted, where the first message is used.
+        #[arg(long, value_name = "PATH")]
+        fixture: Option<PathBuf>,
+
+        /// Render again whenever the templates or the fixture change.
+        #[arg(long)]
+        watch: bool,
+    },
+}
+
+/// The subcommands of `cache`.
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Remove all the cached data.
+    Clear,
+
+    /// Show the location, the number of entries, and the size of the cache.
+    Stats,
+}
+
+impl Args {
+    /// Fill the options which are not given via the command line nor the environment variables,
+    /// with the profile and the defaults in the configuration file, in this order.
+    pub fn apply_config(&mut self) -> Result<()> {
+        let mut config = Config::load(self.config.as_deref())?;
+        let settings = config.settings(self.profile.as_deref())?;
+
+        if self.timezone.is_empty() {
+            self.timezone = settings.timezone.map(Vec::from).unwrap_or_default();
+            self.workspace_timezones = config.workspace_timezones();
+        }
+        self.locale = self.locale.or(settings.locale);
+        self.quote = flag(self.quote, self.no_quote).or(settings.quote).unwrap_or_default();
+        self.reactions = flag(self.reactions, self.no_reactions)
+            .or(settings.reactions)
+            .unwrap_or_default();
+        self.download_files = self.download_files.take().or(settings.download_files);
+        self.preset = self.preset.or(settings.preset);
+        self.templates = std::mem::take(&mut self.templates).or(settings.templates);
+        self.template = self.template.take().or(settings.template);
+        self.templates_dir = self.templates_dir.take().or_else(|| {
+            config_dir()
+                .map(|dir| dir.join("templates"))
+                .filter(|dir| dir.is_dir())
+        });
+        self.workspace_tokens = config.workspace_tokens();
+
+        Ok(())
+    }
+
+    /// The IANA time zone database identifier to use for the date variables, i.e. the first one
+    /// given.
+    pub fn timezone(&self) -> &str {
+        self.timezone.first().map_or(SYSTEM_TIMEZONE.as_str(), String::as_str)
+    }
+
+    /// The IANA time zone database identifiers to use for `zones`, i.e. all the ones given.
+    pub fn timezones(&self) -> Vec<&str> {
+        if self.timezone.is_empty() {
+            vec![SYSTEM_TIMEZONE.as_str()]
+        } else {
+            self.timezone.iter().map(String::as_str).collect()
+        }
+    }
+
+    /// The format of the structured output, if requested.
+    pub fn structured(&self) -> Option<Structured> {
+        if self.json {
+            Some(Structured::Json)
+        } else if self.yaml {
+            Some(Structured::Yaml)
+        } else {
+            None
+        }
+    }
+
+    /// Collect the URLs given as the arguments, and the ones in the input text read by
+    /// [`Args::read_input`]. Blank lines are ignored.
+    pub fn collect_urls(&self) -> Result<Vec<String>> {
+        let mut urls = self.urls.clone();
+
+        if let Some(text) = self.read_input()? {
+            urls.extend(split_urls(&text));
+        }
+
+        Ok(urls)
+    }
+
+    /// Read the text in the file specified by `--from-file`, or in the standard input when no URL
+    /// is given as the arguments and it is not a terminal.
+    pub fn read_input(&self) -> Result<Option<String>> {
+        if let Some(ref path) = self.from_file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|why| anyhow!("failed to read {}: {why}", path.display()))?;
+            return Ok(Some(content));
+        }
+
+        if self.urls.is_empty() && !stdin().is_terminal() {
+            let mut content = String::new();
+            stdin()
+                .read_to_string(&mut content)
+                .map_err(|why| anyhow!("failed to read from standard input: {why}"))?;
+            if !content.trim().is_empty() {
+                return Ok(Some(content));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Split the given text into URLs, one per line, ignoring blank lines.
+pub fn split_urls(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+}
+
+// The value of an option given with a pair of flags to turn it on and off, e.g. `--quote` and
+// `--no-quote`, or `None` if neither is given. Only the last one given is set.
+fn flag(on: bool, off: bool) -> Option<bool> {
+    (on || off).then_some(on)
 }
 
 impl<'a> From<&'a Args> for Uninitialized<'a> {
     fn from(args: &'a Args) -> Self {
         Self {
-            token: args.token.as_str(),
-            quote: args.quote,
-            timezone: args.timezone.as_str(),
+            token: if let Some(ref command) = args.token_command {
+                TokenSource::Command(command)
+            } else if let Some(ref path) = args.token_file {
+                TokenSource::File(path)
+            } else if let Some(ref token) = args.token {
+                TokenSource::Plain(token)
+            } else if args.token_keyring {
+                TokenSource::Keyring
+            } else {
+                TokenSource::None
+            },
+            keyring: args.token_keyring,
+            workspace_tokens: args.workspace_tokens.clone(),
+            export: args.export.as_deref(),
+            no_cache: args.no_cache,
+            refresh: args.refresh,
+            // The structured output and the named templates include the message body.
+            quote: args.quote || args.structured().is_some() || args.template.is_some(),
+            thread: args.thread,
+            reactions: args.reactions,
+            download_files: args.download_files.as_deref(),
+            append_to: args.append_to.as_deref(),
+            timezone: args.timezone(),
+            timezones: args.timezones(),
+            workspace_timezones: args.workspace_timezones.clone(),
+            locale: args.locale,
+            preset: args.preset.unwrap_or_default(),
             templates: args.templates.clone(),
+            templates_dir: args.templates_dir.as_deref(),
+            template: args.template.as_deref(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs::write, process};
+
+    use anyhow::Result;
+    use clap::Parser;
+
+    use crate::{
+        args::{Args, Command, TemplateCommand},
+        template::Preset,
+    };
+
+    #[test]
+    fn test_apply_config() -> Result<()> {
+        let path = temp_dir().join(format!("sam-test-config-{}.toml", process::id()));
+        write(
+            &path,
+            r#"
+timezone = "UTC"
+quote = true
+
+[templates]
+plain_text = "default"
+rich_text = "default rich"
+
+[profile.work]
+timezone = "America/New_York"
+
+[profile.work.templates]
+plain_text = "work"
+
+[workspace.acme]
+timezone = "Europe/Paris"
+"#,
+        )?;
+
+        let apply = |args: &[&str]| -> Result<Args> {
+            let config = path.to_str().unwrap();
+            let mut args = Args::try_parse_from(
+                ["sam", "--token", "xoxp", "--config", config].iter().chain(args),
+            )?;
+            args.apply_config()?;
+            Ok(args)
+        };
+
+        // The defaults in the configuration file.
+        let args = apply(&[])?;
+        assert_eq!(args.timezone, ["UTC"]);
+        assert_eq!(args.workspace_timezones.get("acme").map(String::as_str), Some("Europe/Paris"));
+        assert!(args.quote);
+        assert_eq!(args.templates.plain_text.as_deref(), Some("default"));
+
+        // The profile, falling back to the defaults.
+        let args = apply(&["--profile", "work"])?;
+        assert_eq!(args.timezone, ["America/New_York"]);
+        assert!(args.quote);
+        assert_eq!(args.templates.plain_text.as_deref(), Some("work"));
+        assert_eq!(args.templates.rich_text.as_deref(), Some("default rich"));
+
+        // The command line, over the profile.
+        let args = apply(&[
+            "--profile",
+            "work",
+            "-t",
+            "Europe/Berlin",
+            "--no-quote",
+            "--plain-text",
+            "cli",
+        ])?;
+        assert_eq!(args.timezone, ["Europe/Berlin"]);
+        // The time zone given explicitly takes precedence over the ones of the workspaces.
+        assert!(args.workspace_timezones.is_empty());
+        assert!(!args.quote);
+        assert_eq!(args.templates.plain_text.as_deref(), Some("cli"));
+
+        // The last one of the pair of flags wins.
+        assert!(apply(&["--no-quote", "--quote"])?.quote);
+        assert!(!apply(&["--quote", "--no-quote"])?.quote);
+
+        assert!(apply(&["--profile", "home"]).is_err());
+
+        Ok(())
+    }
+    #[test]
+    fn test_global() -> Result<()> {
+        // The options shared with the subcommands are accepted after them too.
+        let args = Args::try_parse_from(["sam", "vars", "-t", "UTC", "--locale", "ja_JP"])?;
+        assert!(matches!(args.command, Some(Command::Vars { .. })));
+        assert_eq!(args.timezone, ["UTC"]);
+        assert!(args.locale.is_some());
+
+        let args = Args::try_parse_from(["sam", "template", "preview", "link", "--preset", "org"])?;
+        assert!(matches!(
+            args.command,
+            Some(Command::Template { command: TemplateCommand::Preview { .. } })
+        ));
+        assert!(matches!(args.preset, Some(Preset::Org)));
+
+        // The others are not.
+        assert!(Args::try_parse_from(["sam", "vars", "--quote"]).is_err());
+
+        Ok(())
+    }
+}
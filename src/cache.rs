@@ -0,0 +1,215 @@
+use std::{
+    fs::{create_dir_all, read_dir, read_to_string, remove_dir_all, write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use strum::VariantArray;
+use strum_macros::{AsRefStr, VariantArray};
+
+use crate::{slack::Permalink, xdg::cache_dir};
+
+/// An on-disk cache of the data retrieved from the Slack API, to save the API calls and the rate
+/// limit budget. Each entry is a JSON file at `<root>/<kind>/<key>.json`, and expires according
+/// to its [`Kind`].
+pub struct Cache {
+    root: PathBuf,
+
+    /// Ignore the existing entries, but still write the new ones.
+    refresh: bool,
+}
+
+/// The kind of the cached data.
+#[derive(AsRefStr, VariantArray, Debug, Clone, Copy)]
+pub enum Kind {
+    /// The name and the profile of each user by the ID, which might change.
+    #[strum(serialize = "users")]
+    User,
+
+    /// The name, the visibility, the topic, and the purpose of each channel by the ID, which might
+    /// change.
+    #[strum(serialize = "channels")]
+    Channel,
+
+    /// The handle of each user group by the ID, which might change.
+    #[strum(serialize = "usergroups")]
+    Usergroup,
+
+    /// The poster and the body of each message. The mentions in the body are resolved when it is
+    /// cached, so the body gets stale once any of the mentioned names changes. The body is
+    /// resolved again once the message is edited, while the edits, the replies, and the reactions
+    /// themselves are never cached.
+    #[strum(serialize = "messages")]
+    Message,
+}
+
+impl Kind {
+    /// How long the entries of this kind are valid for.
+    fn ttl(self) -> Duration {
+        match self {
+            Kind::User | Kind::Channel | Kind::Usergroup | Kind::Message => {
+                Duration::from_secs(24 * 60 * 60)
+            }
+        }
+    }
+}
+
+impl Cache {
+    /// Open the cache in the XDG cache directory.
+    ///
+    /// # Arguments
+    ///
+    /// - `refresh`: Ignore the existing entries, but still write the new ones.
+    pub fn open(refresh: bool) -> Result<Self> {
+        let root = cache_dir().ok_or_else(|| anyhow!("failed to locate the cache directory"))?;
+        Ok(Self { root, refresh })
+    }
+
+    /// The root directory of the cache.
+    pub fn root(&self) -> &PathBuf {
+        &self.root
+    }
+
+    /// Remove all the cached entries.
+    pub fn clear(&self) -> Result<()> {
+        if self.root.exists() {
+            remove_dir_all(&self.root)?;
+        }
+        Ok(())
+    }
+
+    /// Count the entries and their total size in bytes, for each kind.
+    pub fn stats(&self) -> Result<Vec<(Kind, usize, u64)>> {
+        Kind::VARIANTS
+            .iter()
+            .map(|kind| -> Result<(Kind, usize, u64)> {
+                let dir = self.root.join(kind.as_ref());
+                if !dir.exists() {
+                    return Ok((*kind, 0, 0));
+                }
+
+                let (count, size) = read_dir(dir)?
+                    .filter_map(|entry| entry.ok()?.metadata().ok())
+                    .fold((0, 0), |(count, size), metadata| (count + 1, size + metadata.len()));
+                Ok((*kind, count, size))
+            })
+            .collect()
+    }
+
+    /// The key of the entry of the message the given permalink points to, i.e. the ID of the
+    /// channel and the timestamp, as the timestamps are unique only in a channel.
+    pub fn message_key(permalink: &Permalink) -> String {
+        format!("{}-{}", permalink.channel, permalink.ts)
+    }
+
+    /// Read the entry of the given kind and key, or `None` if it does not exist, has expired, or
+    /// cannot be read.
+    pub fn get<T>(&self, kind: Kind, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        if self.refresh {
+            return None;
+        }
+
+        let path = self.path(kind, key);
+        let modified = path.metadata().ok()?.modified().ok()?;
+        if SystemTime::now().duration_since(modified).unwrap_or_default() > kind.ttl() {
+            return None;
+        }
+
+        serde_json::from_str(&read_to_string(path).ok()?).ok()
+    }
+
+    /// Write the entry of the given kind and key. A failure is not fatal, as the cache is just an
+    /// optimization.
+    pub fn put<T>(&self, kind: Kind, key: &str, value: &T)
+    where
+        T: Serialize,
+    {
+        let path = self.path(kind, key);
+        if let Err(why) = Self::write_entry(&path, value) {
+            eprintln!("failed to write cache {}: {why}", path.display());
+        }
+    }
+
+    fn write_entry<T>(path: &Path, value: &T) -> Result<()>
+    where
+        T: Serialize,
+    {
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        write(path, serde_json::to_string(value)?)?;
+        Ok(())
+    }
+
+    fn path(&self, kind: Kind, key: &str) -> PathBuf {
+        self.root.join(kind.as_ref()).join(format!("{key}.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::File,
+        process,
+        time::{Duration, SystemTime},
+    };
+
+    use anyhow::Result;
+
+    use crate::cache::{Cache, Kind};
+
+    #[test]
+    fn test_cache() -> Result<()> {
+        let root = temp_dir().join(format!("sam-test-cache-{}", process::id()));
+        let cache = Cache { root: root.clone(), refresh: false };
+        cache.clear()?;
+
+        cache.put(Kind::User, "U0123", &"jake");
+        cache.put(Kind::User, "U4567", &"finn");
+        cache.put(Kind::Message, "C0123-1724743664.325609", &"hello");
+        assert_eq!(cache.get::<String>(Kind::User, "U0123").as_deref(), Some("jake"));
+        assert_eq!(cache.get::<String>(Kind::Channel, "C0123"), None);
+
+        // Expire the user and the message, but not the other user.
+        let past = SystemTime::now() - Duration::from_secs(25 * 60 * 60);
+        File::options()
+            .write(true)
+            .open(cache.path(Kind::User, "U0123"))?
+            .set_modified(past)?;
+        File::options()
+            .write(true)
+            .open(cache.path(Kind::Message, "C0123-1724743664.325609"))?
+            .set_modified(past)?;
+        assert_eq!(cache.get::<String>(Kind::User, "U0123"), None);
+        assert_eq!(cache.get::<String>(Kind::User, "U4567").as_deref(), Some("finn"));
+        assert_eq!(cache.get::<String>(Kind::Message, "C0123-1724743664.325609"), None);
+
+        // Ignore the entries, but still write the new ones.
+        let refresh = Cache { root: root.clone(), refresh: true };
+        assert_eq!(refresh.get::<String>(Kind::User, "U4567"), None);
+        refresh.put(Kind::User, "U0123", &"jake2");
+        assert_eq!(cache.get::<String>(Kind::User, "U0123").as_deref(), Some("jake2"));
+
+        let counts = cache
+            .stats()?
+            .into_iter()
+            .map(|(kind, count, _)| (kind.as_ref().to_string(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [("users", 2), ("channels", 0), ("usergroups", 0), ("messages", 1)]
+                .map(|(kind, count)| (kind.to_string(), count))
+        );
+
+        cache.clear()?;
+        assert!(!root.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+
+use crate::slack;
+
+/// A Slack message with the channel and user names resolved, regardless of where it is retrieved
+/// from.
+pub struct Message {
+    /// The name of the channel the message belongs to.
+    pub channel_name: String,
+
+    /// Whether the channel is a private channel or not.
+    pub is_private_channel: bool,
+
+    /// The name of the user who posted the message.
+    pub user_name: String,
+
+    /// The Slack URL of the message.
+    pub url: String,
+
+    /// The timestamp of the message, in microseconds.
+    pub ts: i64,
+
+    /// The metadata of the message, the user, the channel, and the workspace.
+    pub metadata: Metadata,
+
+    /// The body of the message in Markdown.
+    pub body: String,
+}
+
+/// The metadata of a message, which is inserted into the template context as is. So the fields
+/// are named after the [`ContextKey`](crate::template::ContextKey)s, and the unknown ones are
+/// empty rather than `None`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Metadata {
+    /// The real name of the user who posted the message.
+    pub user_real_name: String,
+
+    /// The display name of the user who posted the message.
+    pub user_display_name: String,
+
+    /// The URL of the avatar image of the user who posted the message.
+    pub user_avatar: String,
+
+    /// The title in the profile of the user who posted the message.
+    pub user_title: String,
+
+    /// The IANA time zone database identifier in the profile of the user who posted the message.
+    pub user_tz: String,
+
+    /// The name of the workspace.
+    pub workspace_name: String,
+
+    /// The domain of the workspace, i.e. `<domain>.slack.com`.
+    pub workspace_domain: String,
+
+    /// The topic of the channel.
+    pub channel_topic: String,
+
+    /// The purpose of the channel.
+    pub channel_purpose: String,
+
+    /// Whether the message has been edited.
+    pub is_edited: bool,
+
+    /// The Slack timestamp of the last edit, if edited.
+    pub edited_ts: String,
+
+    /// The number of the replies, if the message is the parent of a thread.
+    pub reply_count: u64,
+
+    /// The reactions to the message, in the order they were first added.
+    pub reactions: Vec<Reaction>,
+
+    /// The files shared with the message.
+    pub files: Vec<File>,
+
+    /// The Slack timestamp of the parent message, if the message is in a thread.
+    pub thread_ts: String,
+}
+
+/// A reaction to a message, as a template context.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reaction {
+    /// The name of the emoji without the colons, e.g. `white_check_mark`.
+    pub name: String,
+
+    /// The emoji itself, e.g. `✅`, or the name in colons, e.g. `:party-parrot:`, for the custom
+    /// ones.
+    pub emoji: String,
+
+    /// The number of the users who reacted with the emoji.
+    pub count: u64,
+}
+
+impl From<&slack::Reaction> for Reaction {
+    fn from(reaction: &slack::Reaction) -> Self {
+        // The skin tone is appended to the name, e.g. `+1::skin-tone-2`, which is ignored.
+        let name = reaction.name.split("::").next().unwrap_or(&reaction.name);
+        Self {
+            name: name.to_string(),
+            emoji: slack::emoji(name),
+            count: reaction.count,
+        }
+    }
+}
+
+/// A file shared with a message, as a template context.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct File {
+    /// The ID of the file.
+    pub id: String,
+
+    /// The name of the file, e.g. `screenshot.png`.
+    pub name: String,
+
+    /// The MIME type of the file, e.g. `image/png`.
+    pub mimetype: String,
+
+    /// The size of the file, in bytes.
+    pub size: u64,
+
+    /// The Slack URL of the file, which shows it in Slack.
+    pub permalink: String,
+
+    /// The URL of the thumbnail, for images, which requires signing in to Slack to show.
+    pub thumbnail: String,
+
+    /// The URL to download the file, which requires the token.
+    pub download_url: String,
+
+    /// The local path the file is downloaded to with `--download-files`, or empty.
+    pub path: String,
+}
+
+impl From<&slack::File> for File {
+    fn from(file: &slack::File) -> Self {
+        Self {
+            id: file.id.clone(),
+            name: file.name.clone(),
+            mimetype: file.mimetype.clone(),
+            size: file.size,
+            permalink: file.permalink.clone(),
+            thumbnail: file.thumb_480.clone(),
+            download_url: file.url_private_download.clone(),
+            path: String::new(),
+        }
+    }
+}
@@ -1,21 +1,144 @@
-use std::{ops::Deref, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env::var_os,
+    fs::{create_dir_all, OpenOptions},
+    io::Write,
+    ops::Deref,
+    path::PathBuf,
+    sync::{LazyLock, Once},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use comrak::{markdown_to_html, ComrakOptions, RenderOptions};
-use slack_client::message_retriever::{state::Resolved, MessageRetriever};
-use state::{Initialized, Retrieved, State, Uninitialized};
-use strum::EnumProperty;
+use futures::{future::join_all, stream, StreamExt, TryStreamExt};
+pub use message::{File, Message, Metadata, Reaction};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use slack_client::message_retriever::MessageRetriever;
+use state::{Initialized, Retrieved, Source, State, Uninitialized};
 use tera::{Context, Tera};
-use tokio::fs::read_to_string;
+use tokens::workspace;
+pub use tokens::{TokenSource, Tokens};
+use zeroize::Zeroizing;
 
-use crate::template::{ContextKey::*, TemplateType::*, Templates};
+use crate::{
+    cache::{Cache, Kind},
+    slack::{
+        self, format_ts, parse_ts, Api, Channel, Export, HttpClient, Permalink, User, Usergroup,
+    },
+    template::{self, ContextKey, ContextKey::*, Locale, NamedTemplate, TemplateType::*},
+};
 
+mod message;
 pub mod state;
+mod tokens;
 
-const TEMPLATE_PLAIN_TEXT: &str = include_str!("../../templates/plain_text");
-const TEMPLATE_PLAIN_TEXT_QUOTE: &str = include_str!("../../templates/plain_text_quote");
-const TEMPLATE_RICH_TEXT: &str = include_str!("../../templates/rich_text");
-const TEMPLATE_RICH_TEXT_QUOTE: &str = include_str!("../../templates/rich_text_quote");
+/// The number of the replies of a thread resolved at once, to stay within the rate limits.
+const THREAD_CONCURRENCY: usize = 4;
+
+/// The IANA time zone database identifier of the system, i.e. `TZ` or `/etc/localtime`, which is
+/// used when no time zone is configured. `UTC` if it cannot be detected.
+pub static SYSTEM_TIMEZONE: LazyLock<String> =
+    LazyLock::new(|| match jiff::tz::TimeZone::system().iana_name() {
+        Some(name) => name.to_string(),
+        None => {
+            eprintln!("failed to detect the time zone of the system, falling back to UTC");
+            "UTC".to_string()
+        }
+    });
+
+/// Whether the failure to look up the workspace has been warned about.
+static TEAM_WARNING: Once = Once::new();
+
+/// The context keys computed from the timestamp of the message.
+static DATE_KEYS: [ContextKey; 22] = [
+    Timestamp,
+    IsoDate,
+    Clock,
+    Year,
+    Year2Digit,
+    Month,
+    MonthAbbrev,
+    Month2Digit,
+    Day,
+    DaySpace,
+    Hour24,
+    Hour12,
+    Minute,
+    Second,
+    AmPm,
+    AmPmLower,
+    Weekday,
+    WeekdayAbbrev,
+    TzIana,
+    TzAbbrev,
+    Offset,
+    OffsetColon,
+];
+
+/// A message in the thread, as a template context.
+#[derive(Serialize)]
+struct ThreadMessage {
+    user_name: String,
+    url: String,
+    ts: String,
+    text: Vec<String>,
+    html: String,
+    #[serde(flatten)]
+    metadata: Metadata,
+    edited_timestamp: String,
+    #[serde(flatten)]
+    dates: BTreeMap<&'static str, String>,
+    zones: Vec<Zone>,
+}
+
+/// The date variables of a message in one of the time zones, as a template context.
+#[derive(Serialize)]
+struct Zone {
+    zone: String,
+    dates: BTreeMap<&'static str, String>,
+}
+
+/// The poster and the body of a message, as cached by the key of the message. The body is `None`
+/// until the message is retrieved with the body, and is as of the last edit at `edited_ts`.
+#[derive(Serialize, Deserialize)]
+struct Posted {
+    user: Option<String>,
+    username: Option<String>,
+    #[serde(default)]
+    edited_ts: Option<String>,
+    body: Option<String>,
+}
+
+/// The Web API with the cache if enabled, to look up the users and the channels by their IDs.
+struct Lookup<'a> {
+    api: Api<'a>,
+    cache: Option<&'a Cache>,
+}
+
+impl ThreadMessage {
+    // Make a message in the thread from the given Slack message, with the dates in the given
+    // time zone and locale, and in each of the given time zones for `zones`.
+    fn new(
+        message: Message,
+        timezone: &str,
+        timezones: &[&str],
+        locale: Option<Locale>,
+    ) -> Result<Self> {
+        let datetime = jiff::Timestamp::from_microsecond(message.ts)?.in_tz(timezone)?;
+
+        Ok(Self {
+            edited_timestamp: edited_timestamp(&message, timezone, locale)?,
+            user_name: message.user_name,
+            url: message.url,
+            ts: format_ts(message.ts),
+            text: message.body.lines().map(String::from).collect(),
+            html: to_html(&message.body),
+            metadata: message.metadata,
+            dates: dates(&datetime, locale).collect(),
+            zones: zones(message.ts, timezones, locale)?,
+        })
+    }
+}
 
 pub struct Client<S>
 where
@@ -24,6 +147,20 @@ where
     state: S,
 }
 
+/// Convert the given Markdown text to HTML. Raw HTML in the text is kept as is.
+pub fn to_html(markdown: &str) -> String {
+    let mut comrak_options = ComrakOptions {
+        render: RenderOptions::builder().unsafe_(true).escape(false).build(),
+        ..ComrakOptions::default()
+    };
+    comrak_options.extension.autolink = true;
+    comrak_options.extension.strikethrough = true;
+    comrak_options.extension.table = true;
+    comrak_options.extension.tasklist = true;
+    comrak_options.extension.tagfilter = true;
+    markdown_to_html(markdown, &comrak_options)
+}
+
 impl<S> Deref for Client<S>
 where
     S: State,
@@ -35,59 +172,55 @@ where
     }
 }
 
-impl<'template> Client<Uninitialized<'_>> {
-    /// Create a new Copier client with the given Slack API token, quote flag, timezone, and
-    /// templates.
+impl Client<Uninitialized<'_>> {
+    /// Create a new Copier client with the given Slack API tokens or workspace export, quote flag,
+    /// timezone, and templates.
     pub async fn from(state: Uninitialized<'_>) -> Result<Client<Initialized>> {
+        let source = match state.export {
+            Some(path) => Source::Export(Export::open(path)?),
+            None => Source::Api(Tokens {
+                default: state.token.read().await?,
+                workspaces: state
+                    .workspace_tokens
+                    .into_iter()
+                    .map(|(workspace, token)| (workspace, Zeroizing::new(token)))
+                    .collect(),
+                keyring: state.keyring,
+            }),
+        };
+
+        let cache = if state.no_cache { None } else { Some(Cache::open(state.refresh)?) };
+
+        let (sources, templates) =
+            template::load(&state.templates, state.preset, state.templates_dir).await?;
+        let tera = template::setup_tera(&sources, state.timezone, state.locale)?;
+        let template = templates.get(state.template.unwrap_or(if state.thread {
+            "thread"
+        } else if state.quote {
+            "quote"
+        } else {
+            "link"
+        }))?;
+
         Ok(Client {
             state: Initialized {
-                token: state.token,
+                source,
+                http: HttpClient::new(),
+                cache,
                 quote: state.quote,
+                thread: state.thread,
+                reactions: state.reactions,
+                download_files: state.download_files,
+                append_to: state.append_to,
                 timezone: state.timezone,
-                tera: Self::setup_tera(&state.templates).await?,
+                timezones: state.timezones,
+                workspace_timezones: state.workspace_timezones,
+                locale: state.locale,
+                tera,
+                template,
             },
         })
     }
-
-    // Set up the Tera template engine with the given [`Templates`], which might contain paths to
-    // the template file, or just the template string.
-    #[rustfmt::skip]
-    async fn setup_tera(arg: &'template Templates) -> Result<Tera> {
-        let mut tera = Tera::default();
-
-        for (name, pathlike, default) in [
-            (PlainText,      &arg.plain_text,       TEMPLATE_PLAIN_TEXT),
-            (PlainTextQuote, &arg.plain_text_quote, TEMPLATE_PLAIN_TEXT_QUOTE),
-            (RichText,       &arg.rich_text,        TEMPLATE_RICH_TEXT),
-            (RichTextQuote,  &arg.rich_text_quote,  TEMPLATE_RICH_TEXT_QUOTE),
-        ] {
-            tera.add_raw_template(name.as_ref(), Self::get_template(pathlike, default).await)?;
-        }
-
-        Ok(tera)
-    }
-
-    // Resolve the template content from the given pathlike. If the pathlike is not a valid path,
-    // then return content as is assuming it's a template string. If no pathlike is given, then
-    // return the default template string.
-    async fn get_template(
-        input: &'template Option<String>,
-        default: &'template str,
-    ) -> &'template str {
-        match input {
-            Some(pathlike) => {
-                if PathBuf::from(&pathlike).exists() {
-                    let content = read_to_string(&pathlike).await.unwrap_or_default();
-                    // Leak the content to make it have a static lifetime.
-                    Box::leak(content.into_boxed_str())
-                } else {
-                    // Leak the content to make it have a static lifetime.
-                    Box::leak(pathlike.clone().into_boxed_str())
-                }
-            }
-            None => default,
-        }
-    }
 }
 
 impl<'state> Client<Initialized<'state>> {
@@ -97,102 +230,945 @@ impl<'state> Client<Initialized<'state>> {
     ///
     /// - `url`: The [`url::URL`] of the Slack message.
     pub async fn retrieve(&self, url: &url::Url) -> Result<Client<Retrieved>> {
-        let mut retriever = MessageRetriever::try_new(url, self.token)?;
-        let message = retriever.resolve(self.quote).await?;
+        // The plain text quote template is used to append the message to a file.
+        let with_body = self.quote || self.append_to.is_some();
+        let mut message = self.resolve(url, with_body).await?;
+        self.download_files(url, &mut message).await?;
+        let (timezone, timezones) = self.timezones_for(url);
+        let mut context =
+            setup_context(&message, timezone, &timezones, self.locale, with_body, self.reactions)?;
+
+        if self.thread {
+            let thread = self.retrieve_thread(url, timezone, &timezones).await?;
+            context.insert(Messages.as_ref(), &thread);
+        }
+
+        // The filters use the time zone of the workspace, too.
+        let mut tera = self.tera.clone();
+        template::register_filters(&mut tera, timezone, self.locale)?;
 
         Ok(Client {
             state: Retrieved {
                 quote: self.quote,
-                tera: self.tera.clone(),
-                context: self.setup_context(&message, self.timezone).await?,
+                thread: self.thread,
+                append_to: self.append_to.map(String::from),
+                tera,
+                template: self.template.clone(),
+                context,
             },
         })
     }
 
-    // Set up the Tera template context from the Slack message just retrieved.
-    async fn setup_context(
-        &self,
-        message: &MessageRetriever<Resolved<'state>>,
-        timezone: &str,
-    ) -> Result<Context> {
-        let mut context = Context::new();
-        let datetime = jiff::Timestamp::from_microsecond(message.ts)?.in_tz(timezone)?;
+    // The time zone for the date variables of the message the given URL points to, i.e. the one
+    // configured for the workspace if any, and the ones for `zones` along with it.
+    fn timezones_for(&self, url: &url::Url) -> (&str, Vec<&str>) {
+        match workspace(url)
+            .ok()
+            .and_then(|workspace| self.workspace_timezones.get(workspace))
+        {
+            Some(timezone) => {
+                let timezone = timezone.as_str();
+                let mut timezones = vec![timezone];
+                timezones.extend(self.timezones.iter().filter(|tz| **tz != timezone));
+                (timezone, timezones)
+            }
+            None => (self.timezone, self.timezones.clone()),
+        }
+    }
 
-        context.insert(ChannelName.as_ref(), &message.channel_name);
-        context.insert(IsPrivateChannel.as_ref(), &message.is_private_channel);
-        context.insert(UserName.as_ref(), &message.user_name);
-        context.insert(Url.as_ref(), &message.url.as_str());
+    // Resolve the message the given URL points to, from the configured source. The body is
+    // resolved only when `with_body` is true, as it is costly with the Web API. The users, the
+    // channels, and the messages retrieved from the Web API are cached if enabled.
+    async fn resolve(&self, url: &url::Url, with_body: bool) -> Result<Message> {
+        match self.source {
+            Source::Api(ref tokens) => {
+                let permalink = Permalink::try_from(url)?;
+                let token = tokens.for_url(url)?;
+                let lookup = Lookup {
+                    api: Api::new(&self.http, &token),
+                    cache: self.cache.as_ref(),
+                };
+                let (posted, message) = lookup.posted(url, &token, &permalink, with_body).await?;
 
-        if self.quote {
-            context.insert(Text.as_ref(), &message.body.lines().collect::<Vec<_>>());
+                let channel = lookup.channel(&permalink.channel).await.map_err(|why| {
+                    anyhow!("failed to look up channel {}: {why}", permalink.channel)
+                })?;
+                let user = match posted.user {
+                    Some(ref id) => lookup
+                        .user(id)
+                        .await
+                        .map_err(|why| anyhow!("failed to look up user {id}: {why}"))?,
+                    None => User::default(),
+                };
+                let metadata = match message {
+                    // The metadata is used only with the body, i.e. by the quote and the thread
+                    // templates and the structured output, so save the lookups for a link.
+                    Some(message) if with_body => {
+                        metadata(&lookup.api, url, &user, &channel, message).await?
+                    }
+                    _ => Metadata::default(),
+                };
 
-            let mut comrak_options = ComrakOptions {
-                render: RenderOptions::builder().unsafe_(true).escape(false).build(),
-                ..ComrakOptions::default()
-            };
-            comrak_options.extension.autolink = true;
-            comrak_options.extension.strikethrough = true;
-            comrak_options.extension.table = true;
-            comrak_options.extension.tasklist = true;
-            comrak_options.extension.tagfilter = true;
-            context.insert(Html.as_ref(), &markdown_to_html(&message.body, &comrak_options));
+                Ok(Message {
+                    channel_name: channel.name,
+                    is_private_channel: channel.is_private,
+                    user_name: user_name(&user)
+                        .or(posted.username)
+                        .unwrap_or_else(|| "unknown".to_string()),
+                    url: url.to_string(),
+                    ts: parse_ts(&permalink.ts)?,
+                    metadata,
+                    body: posted.body.unwrap_or_default(),
+                })
+            }
+            Source::Export(ref export) => export.resolve(url),
         }
+    }
 
-        [
-            Timestamp,
-            IsoDate,
-            Clock,
-            Year,
-            Year2Digit,
-            Month,
-            MonthAbbrev,
-            Month2Digit,
-            Day,
-            DaySpace,
-            Hour24,
-            Hour12,
-            Minute,
-            Second,
-            AmPm,
-            AmPmLower,
-            Weekday,
-            WeekdayAbbrev,
-            TzIana,
-            TzAbbrev,
-            Offset,
-            OffsetColon,
-        ]
-        .iter()
-        .for_each(|key| {
-            context.insert(
-                key.as_ref(),
-                &datetime.strftime(key.get_str("format").unwrap()).to_string(),
-            )
-        });
+    // Download the files shared with the given message to the directory given with
+    // `--download-files`, if any, and set the local paths of them. The files already downloaded
+    // are not downloaded again.
+    async fn download_files(&self, url: &url::Url, message: &mut Message) -> Result<()> {
+        let Some(dir) = self.download_files else {
+            return Ok(());
+        };
+        if message.metadata.files.is_empty() {
+            return Ok(());
+        }
+
+        create_dir_all(dir).map_err(|why| anyhow!("failed to create {}: {why}", dir.display()))?;
+        let token = match self.source {
+            Source::Api(ref tokens) => Some(tokens.for_url(url)?),
+            Source::Export(_) => None,
+        };
 
-        Ok(context)
+        for file in message.metadata.files.iter_mut() {
+            if file.download_url.is_empty() {
+                continue;
+            }
+            // The names are not unique, so prefix the ID. Keep the name safe for the file system
+            // and the markup languages, i.e. without the path separators nor the spaces.
+            let name = file
+                .name
+                .chars()
+                .map(|c| if c.is_alphanumeric() || "._-".contains(c) { c } else { '_' })
+                .collect::<String>();
+            let path = dir.join(format!("{}-{name}", file.id));
+            if !path.exists() {
+                slack::download(
+                    &self.http,
+                    &file.download_url,
+                    token.as_deref().map(String::as_str),
+                    &path,
+                )
+                .await?;
+            }
+            file.path = path.display().to_string();
+        }
+
+        Ok(())
+    }
+
+    // Retrieve the parent message and all the replies of the thread the given URL points to, with
+    // the dates in the given time zones. A message which is not in a thread is considered as a
+    // thread of one message.
+    async fn retrieve_thread(
+        &self,
+        url: &url::Url,
+        timezone: &str,
+        timezones: &[&str],
+    ) -> Result<Vec<ThreadMessage>> {
+        let permalink = Permalink::try_from(url)?;
+        let thread_ts = permalink.thread_ts.as_deref().unwrap_or(&permalink.ts);
+        let replies: Vec<String> = match self.source {
+            Source::Api(ref tokens) => Api::new(&self.http, &tokens.for_url(url)?)
+                .replies(&permalink.channel, thread_ts)
+                .await?
+                .into_iter()
+                .map(|reply| reply.ts)
+                .collect(),
+            Source::Export(ref export) => export.replies(&permalink.channel, thread_ts)?,
+        };
+        let urls = thread_urls(&permalink, thread_ts, replies)?;
+
+        stream::iter(urls)
+            .map(|url| async move {
+                let mut message = self.resolve(&url, true).await?;
+                self.download_files(&url, &mut message).await?;
+                ThreadMessage::new(message, timezone, timezones, self.locale)
+            })
+            .buffered(THREAD_CONCURRENCY)
+            .try_collect()
+            .await
     }
 }
 
 impl Client<Retrieved> {
+    /// Make a client ready to render the given template context with the given template, without
+    /// retrieving any message, to preview the template.
+    pub fn preview(tera: Tera, template: NamedTemplate, context: Context) -> Self {
+        Client {
+            state: Retrieved {
+                quote: true,
+                thread: false,
+                append_to: None,
+                tera,
+                template,
+                context,
+            },
+        }
+    }
+
     /// Render the Slack message into a rich text and a plain text.
     ///
     /// # Returns
     ///
     /// A tuple of the rich text and the plain text [`String`].
     pub fn render(&self) -> Result<(String, String)> {
-        let (rich_text, text) = if self.quote {
+        let render = |name: &Option<String>| {
+            name.as_ref()
+                .map(|name| self.tera.render(name, &self.context))
+                .transpose()
+        };
+
+        // A template which produces only one of them stands in for the other.
+        Ok(match (render(&self.template.html)?, render(&self.template.text)?) {
+            (Some(rich_text), Some(text)) => (rich_text, text),
+            (Some(rich_text), None) => (rich_text.clone(), rich_text),
+            (None, Some(text)) => (to_html(&text), text),
+            (None, None) => (String::new(), String::new()),
+        })
+    }
+
+    /// Render the given Slack messages into one combined rich text and plain text, in the given
+    /// order.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the rich text and the plain text [`String`].
+    pub fn render_all(messages: &[Self]) -> Result<(String, String)> {
+        let (rich_texts, texts) = messages
+            .iter()
+            .map(|message| message.render())
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+
+        // Quotes and threads are separated by a blank line, links are listed one per line.
+        let quote = messages
+            .first()
+            .is_some_and(|message| message.quote || message.thread);
+        let (rich_text_separator, text_separator) =
+            if quote { ("\n", "\n\n") } else { ("<br>\n", "\n") };
+
+        Ok((
+            rich_texts
+                .iter()
+                .map(|s| s.trim())
+                .collect::<Vec<_>>()
+                .join(rich_text_separator),
+            texts
+                .iter()
+                .map(|s| s.trim())
+                .collect::<Vec<_>>()
+                .join(text_separator),
+        ))
+    }
+
+    /// Get the template context of the Slack message as a JSON value.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.context.clone().into_json()
+    }
+
+    /// Append the Slack message rendered with the plain text quote template to the file, if
+    /// `append_to` is set. The path is rendered as a template with the message context, and a
+    /// leading `~/` is expanded to the home directory. If the file does not exist, it is created
+    /// with the header template rendered first. The messages are separated by a blank line.
+    ///
+    /// # Returns
+    ///
+    /// The path to the file the message is appended to, if any.
+    pub fn append(&self) -> Result<Option<PathBuf>> {
+        let Some(ref path) = self.append_to else {
+            return Ok(None);
+        };
+
+        let path = expand_home(Tera::one_off(path, &self.context, false)?.trim());
+
+        let mut content = String::new();
+        if path.exists() {
+            let existing = std::fs::read_to_string(&path)?;
+            if !existing.is_empty() && !existing.ends_with("\n\n") {
+                content.push_str(if existing.ends_with('\n') { "\n" } else { "\n\n" });
+            }
+        } else {
+            if let Some(parent) = path.parent() {
+                create_dir_all(parent)?;
+            }
+            let header = self.tera.render(AppendHeader.as_ref(), &self.context)?;
+            if !header.trim().is_empty() {
+                content.push_str(header.trim_end());
+                content.push_str("\n\n");
+            }
+        }
+        content.push_str(self.tera.render(PlainTextQuote.as_ref(), &self.context)?.trim());
+        content.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(content.as_bytes())?;
+
+        Ok(Some(path))
+    }
+}
+
+impl Lookup<'_> {
+    /// Read the entry of the given kind and key from the cache, if enabled.
+    fn get<T>(&self, kind: Kind, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.cache.and_then(|cache| cache.get(kind, key))
+    }
+
+    /// Write the entry of the given kind and key to the cache, if enabled.
+    fn put<T>(&self, kind: Kind, key: &str, value: &T)
+    where
+        T: Serialize,
+    {
+        if let Some(cache) = self.cache {
+            cache.put(kind, key, value);
+        }
+    }
+
+    /// Look up the poster and the body of the message the given URL and permalink point to, along
+    /// with the message itself unless the cached poster is enough, i.e. without the body. The
+    /// message is never cached, as the edits, the replies, and the reactions change at any time,
+    /// while the body is resolved again only once the message is edited.
+    async fn posted(
+        &self,
+        url: &url::Url,
+        token: &str,
+        permalink: &Permalink,
+        with_body: bool,
+    ) -> Result<(Posted, Option<slack::Message>)> {
+        let key = Cache::message_key(permalink);
+        let cached = match self.get::<Posted>(Kind::Message, &key) {
+            Some(posted) if !with_body => return Ok((posted, None)),
+            cached => cached,
+        };
+
+        let message = self
+            .api
+            .message(&permalink.channel, permalink.thread_ts.as_deref(), &permalink.ts)
+            .await?;
+        let edited_ts = message.edited.as_ref().map(|edited| edited.ts.clone());
+        if let Some(posted) = cached {
+            if posted.body.is_some() && posted.edited_ts == edited_ts {
+                return Ok((posted, Some(message)));
+            }
+        }
+
+        let body = if !with_body {
+            None
+        } else if message.content.has_layout() {
+            // slack_client converts only the rich text blocks, so the other layouts such as the
+            // section blocks or the attachments are converted here instead.
+            let names = self.names(message.content.mentions()).await;
+            Some(message.content.to_markdown(&|id: &str| names.get(id).cloned()))
+        } else {
+            // The body is converted into Markdown by slack_client, with the mentions.
+            let mut retriever = MessageRetriever::try_new(url, token)?;
+            Some(retriever.resolve(true).await?.body.to_string())
+        };
+
+        let posted = Posted {
+            user: message.user.clone(),
+            username: message.username.clone(),
+            edited_ts,
+            body,
+        };
+        self.put(Kind::Message, &key, &posted);
+        Ok((posted, Some(message)))
+    }
+
+    /// Look up the user with the given ID.
+    async fn user(&self, id: &str) -> Result<User> {
+        if let Some(user) = self.get(Kind::User, id) {
+            return Ok(user);
+        }
+        let user = self.api.user(id).await?;
+        self.put(Kind::User, id, &user);
+        Ok(user)
+    }
+
+    /// Look up the channel with the given ID.
+    async fn channel(&self, id: &str) -> Result<Channel> {
+        if let Some(channel) = self.get(Kind::Channel, id) {
+            return Ok(channel);
+        }
+        let channel = self.api.channel(id).await?;
+        self.put(Kind::Channel, id, &channel);
+        Ok(channel)
+    }
+
+    /// Look up the handles of the user groups with the given IDs. All the user groups are listed
+    /// at once, as there is no method to retrieve one, but only if any of them is not cached.
+    async fn usergroups(&self, ids: &BTreeSet<String>) -> Result<HashMap<String, String>> {
+        let mut handles = ids
+            .iter()
+            .filter_map(|id| {
+                let usergroup = self.get::<Usergroup>(Kind::Usergroup, id)?;
+                Some((usergroup.id, usergroup.handle))
+            })
+            .collect::<HashMap<_, _>>();
+        if handles.len() < ids.len() {
+            for usergroup in self.api.usergroups().await? {
+                self.put(Kind::Usergroup, &usergroup.id, &usergroup);
+                if ids.contains(&usergroup.id) {
+                    handles.insert(usergroup.id, usergroup.handle);
+                }
+            }
+        }
+        Ok(handles)
+    }
+
+    /// Look up the names of the given users, user groups, and channels, for the mentions. The ones
+    /// not found, e.g. without the scopes, are left out, and shown as the IDs.
+    async fn names(&self, ids: BTreeSet<String>) -> HashMap<String, String> {
+        let (usergroups, ids): (BTreeSet<_>, BTreeSet<_>) =
+            ids.into_iter().partition(|id| id.starts_with('S'));
+        let usergroups = self.usergroups(&usergroups).await.unwrap_or_default();
+
+        join_all(ids.into_iter().map(|id| async move {
+            let name = if id.starts_with(['C', 'D', 'G']) {
+                self.channel(&id).await.ok().map(|channel| channel.name)
+            } else {
+                self.user(&id).await.ok().as_ref().and_then(user_name)
+            };
+            name.filter(|name| !name.is_empty()).map(|name| (id, name))
+        }))
+        .await
+        .into_iter()
+        .flatten()
+        .chain(usergroups)
+        .collect()
+    }
+}
+
+// The name of the given user, i.e. the display name, or the real name if it is empty.
+fn user_name(user: &User) -> Option<String> {
+    user.profile
+        .display_name
+        .clone()
+        .filter(|name| !name.is_empty())
+        .or_else(|| user.real_name.clone())
+        .filter(|name| !name.is_empty())
+}
+
+// The URLs of the messages of the thread started by `thread_ts` in the channel of the given
+// permalink, with the given timestamps, in chronological order, i.e. the parent first.
+fn thread_urls(
+    permalink: &Permalink,
+    thread_ts: &str,
+    ts: impl IntoIterator<Item = String>,
+) -> Result<Vec<url::Url>> {
+    let mut ts = ts
+        .into_iter()
+        .map(|ts| Ok((parse_ts(&ts)?, ts)))
+        .collect::<Result<Vec<_>>>()?;
+    ts.sort();
+    ts.iter().map(|(_, ts)| permalink.url_for(ts, thread_ts)).collect()
+}
+
+// Expand the leading `~/` of the given path to the home directory, if any.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// The metadata of the given message, the user who posted it, the channel, and the workspace the
+// given URL belongs to.
+async fn metadata(
+    api: &Api<'_>,
+    url: &url::Url,
+    user: &User,
+    channel: &Channel,
+    message: slack::Message,
+) -> Result<Metadata> {
+    // `team.info` needs the `team:read` scope, which is not required otherwise, so fall back to the
+    // domain in the URL. Warn only once, as it fails for every message without the scope.
+    let team = api
+        .team()
+        .await
+        .inspect_err(|why| {
+            TEAM_WARNING.call_once(|| {
+                eprintln!("failed to look up the workspace, using the domain of the URL: {why}")
+            })
+        })
+        .ok();
+
+    Ok(Metadata {
+        user_real_name: user.real_name.clone().unwrap_or_default(),
+        user_display_name: user.profile.display_name.clone().unwrap_or_default(),
+        user_avatar: user.profile.image_192.clone().unwrap_or_default(),
+        user_title: user.profile.title.clone().unwrap_or_default(),
+        user_tz: user.tz.clone().unwrap_or_default(),
+        workspace_name: team.as_ref().map(|team| team.name.clone()).unwrap_or_default(),
+        workspace_domain: match team {
+            Some(team) => team.domain,
+            None => workspace(url)?.to_string(),
+        },
+        channel_topic: channel.topic.value.clone(),
+        channel_purpose: channel.purpose.value.clone(),
+        is_edited: message.edited.is_some(),
+        edited_ts: message.edited.map(|edited| edited.ts).unwrap_or_default(),
+        reply_count: message.reply_count.unwrap_or_default(),
+        reactions: message.reactions.iter().map(Reaction::from).collect(),
+        files: message.files.iter().map(File::from).collect(),
+        thread_ts: message.thread_ts.unwrap_or_default(),
+    })
+}
+
+/// Build the template context of a sample message in the given time zone and locale, with
+/// `zones` in each of the given time zones, to preview the templates without retrieving any
+/// message. The reaction line is shown when `reactions` is true.
+pub fn sample_context(
+    timezone: &str,
+    timezones: &[&str],
+    locale: Option<Locale>,
+    reactions: bool,
+) -> Result<Context> {
+    let message = Message {
+        channel_name: "general".to_string(),
+        is_private_channel: false,
+        user_name: "jake".to_string(),
+        url: "https://xxx.slack.com/archives/C01234567/p1724261952503309".to_string(),
+        ts: 1724261952503309,
+        metadata: Metadata {
+            user_real_name: "Jake Doe".to_string(),
+            user_display_name: "jake".to_string(),
+            user_avatar: "https://avatars.slack-edge.com/jake_192.png".to_string(),
+            user_title: "Software Engineer".to_string(),
+            user_tz: "America/Los_Angeles".to_string(),
+            workspace_name: "XXX".to_string(),
+            workspace_domain: "xxx".to_string(),
+            channel_topic: "Company-wide announcements".to_string(),
+            channel_purpose: "This channel is for team-wide communication.".to_string(),
+            is_edited: true,
+            edited_ts: "1724262012.000000".to_string(),
+            reply_count: 0,
+            reactions: vec![
+                Reaction {
+                    name: "white_check_mark".to_string(),
+                    emoji: "✅".to_string(),
+                    count: 5,
+                },
+                Reaction {
+                    name: "eyes".to_string(),
+                    emoji: "👀".to_string(),
+                    count: 2,
+                },
+            ],
+            files: vec![File {
+                id: "F01234567".to_string(),
+                name: "screenshot.png".to_string(),
+                mimetype: "image/png".to_string(),
+                size: 123456,
+                permalink: "https://xxx.slack.com/files/U01234567/F01234567/screenshot.png"
+                    .to_string(),
+                thumbnail:
+                    "https://files.slack.com/files-tmb/T01234567-F01234567/screenshot_480.png"
+                        .to_string(),
+                download_url:
+                    "https://files.slack.com/files-pri/T01234567-F01234567/download/screenshot.png"
+                        .to_string(),
+                path: String::new(),
+            }],
+            thread_ts: String::new(),
+        },
+        body: "Hello, world!\n\nThis is a **sample** message.".to_string(),
+    };
+
+    let mut context = setup_context(&message, timezone, timezones, locale, true, reactions)?;
+    let thread = [ThreadMessage::new(message, timezone, timezones, locale)?];
+    context.insert(Messages.as_ref(), &thread);
+
+    Ok(context)
+}
+
+// Set up the Tera template context from the given Slack message, with the dates in the given time
+// zone, and in each of the given time zones for `zones`. The body is included only when
+// `with_body` is true, and the reaction line is shown only when `reactions` is true.
+fn setup_context(
+    message: &Message,
+    timezone: &str,
+    timezones: &[&str],
+    locale: Option<Locale>,
+    with_body: bool,
+    reactions: bool,
+) -> Result<Context> {
+    let mut context = Context::new();
+    let datetime = jiff::Timestamp::from_microsecond(message.ts)?.in_tz(timezone)?;
+
+    context.insert(ChannelName.as_ref(), &message.channel_name);
+    context.insert(IsPrivateChannel.as_ref(), &message.is_private_channel);
+    context.insert(UserName.as_ref(), &message.user_name);
+    context.insert(Url.as_ref(), &message.url);
+    context.insert(Ts.as_ref(), &format_ts(message.ts));
+    context.insert(SystemTz.as_ref(), SYSTEM_TIMEZONE.as_str());
+    context.extend(Context::from_serialize(&message.metadata)?);
+    context.insert(EditedTimestamp.as_ref(), &edited_timestamp(message, timezone, locale)?);
+    context.insert(ShowReactions.as_ref(), &reactions);
+
+    if with_body {
+        context.insert(Text.as_ref(), &message.body.lines().collect::<Vec<_>>());
+        context.insert(Html.as_ref(), &to_html(&message.body));
+    }
+
+    dates(&datetime, locale).for_each(|(key, value)| context.insert(key, &value));
+    context.insert(Zones.as_ref(), &zones(message.ts, timezones, locale)?);
+
+    Ok(context)
+}
+
+// Format the time of the last edit of the given message in the given time zone and locale, in the
+// same way as `timestamp`, or empty if it has not been edited.
+fn edited_timestamp(message: &Message, timezone: &str, locale: Option<Locale>) -> Result<String> {
+    if message.metadata.edited_ts.is_empty() {
+        return Ok(String::new());
+    }
+
+    let datetime = jiff::Timestamp::from_microsecond(parse_ts(&message.metadata.edited_ts)?)?
+        .in_tz(timezone)?;
+    let format = EditedTimestamp.localized_format(locale).unwrap();
+    Ok(template::strftime(&datetime, format, locale))
+}
+
+// Format the given datetime for each of the [`DATE_KEYS`], in the given locale if any.
+fn dates(
+    datetime: &jiff::Zoned,
+    locale: Option<Locale>,
+) -> impl Iterator<Item = (&'static str, String)> + '_ {
+    DATE_KEYS.iter().map(move |key| {
+        let format = key.localized_format(locale).unwrap();
+        (key.as_ref(), template::strftime(datetime, format, locale))
+    })
+}
+
+// Format the given timestamp for each of the [`DATE_KEYS`] in each of the given time zones, in
+// the given order.
+fn zones(ts: i64, timezones: &[&str], locale: Option<Locale>) -> Result<Vec<Zone>> {
+    let timestamp = jiff::Timestamp::from_microsecond(ts)?;
+    timezones
+        .iter()
+        .map(|timezone| -> Result<_> {
+            let datetime = timestamp.in_tz(timezone)?;
+            Ok(Zone {
+                zone: timezone.to_string(),
+                dates: dates(&datetime, locale).collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        env::{temp_dir, var_os},
+        fs::{read_to_string, remove_dir_all, write},
+        path::PathBuf,
+        process,
+    };
+
+    use anyhow::Result;
+    use tera::{Context, Tera};
+
+    use crate::{
+        client::{
+            expand_home, sample_context,
+            state::{Initialized, Retrieved, Source},
+            thread_urls, Client, File, Reaction,
+        },
+        slack::{self, Export, HttpClient, Permalink},
+        template::{self, NamedTemplate, NamedTemplates, Preset, TemplateType::*, Templates},
+    };
+
+    #[test]
+    fn test_render_all() -> Result<()> {
+        let mut tera = Tera::default();
+        tera.add_raw_templates([
+            (PlainText.as_ref(), "[{{ user_name }}]({{ url }})"),
+            (RichText.as_ref(), "<a href=\"{{ url }}\">{{ user_name }}</a>"),
+            (PlainTextQuote.as_ref(), "> {{ user_name }}\n"),
+            (RichTextQuote.as_ref(), "<blockquote>{{ user_name }}</blockquote>\n"),
+        ])?;
+        let messages = |quote: bool| {
+            ["jake", "finn"]
+                .map(|user_name| {
+                    let mut context = Context::new();
+                    context.insert("user_name", user_name);
+                    context.insert("url", &format!("https://xxx.slack.com/{user_name}"));
+                    Client {
+                        state: Retrieved {
+                            quote,
+                            thread: false,
+                            append_to: None,
+                            tera: tera.clone(),
+                            template: NamedTemplates::builtin()
+                                .get(if quote { "quote" } else { "link" })
+                                .unwrap(),
+                            context,
+                        },
+                    }
+                })
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            Client::<Retrieved>::render_all(&messages(false))?,
             (
-                self.tera.render(RichTextQuote.as_ref(), &self.context)?,
-                self.tera.render(PlainTextQuote.as_ref(), &self.context)?,
+                "<a href=\"https://xxx.slack.com/jake\">jake</a><br>\n<a href=\"https://xxx.slack.com/finn\">finn</a>".to_string(),
+                "[jake](https://xxx.slack.com/jake)\n[finn](https://xxx.slack.com/finn)".to_string(),
             )
-        } else {
+        );
+        assert_eq!(
+            Client::<Retrieved>::render_all(&messages(true))?,
             (
-                self.tera.render(RichText.as_ref(), &self.context)?,
-                self.tera.render(PlainText.as_ref(), &self.context)?,
+                "<blockquote>jake</blockquote>\n<blockquote>finn</blockquote>".to_string(),
+                "> jake\n\n> finn".to_string(),
             )
+        );
+        assert_eq!(Client::<Retrieved>::render_all(&[])?, (String::new(), String::new()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_urls() -> Result<()> {
+        let permalink = Permalink::try_from(&url::Url::parse(
+            "https://xxx.slack.com/archives/C01234567/p1724743664325609?thread_ts=1724743600.000100&cid=C01234567",
+        )?)?;
+        let ts =
+            ["1724743664.325609", "1724743600.000100", "1724743999.000001", "1724743700.000000"];
+
+        assert_eq!(
+            thread_urls(&permalink, "1724743600.000100", ts.map(String::from))?
+                .iter()
+                .map(url::Url::as_str)
+                .collect::<Vec<_>>(),
+            [
+                "https://xxx.slack.com/archives/C01234567/p1724743600000100",
+                "https://xxx.slack.com/archives/C01234567/p1724743664325609?thread_ts=1724743600.000100&cid=C01234567",
+                "https://xxx.slack.com/archives/C01234567/p1724743700000000?thread_ts=1724743600.000100&cid=C01234567",
+                "https://xxx.slack.com/archives/C01234567/p1724743999000001?thread_ts=1724743600.000100&cid=C01234567",
+            ]
+        );
+        assert!(thread_urls(&permalink, "1724743600.000100", ["x".to_string()]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_append() -> Result<()> {
+        let dir = temp_dir().join(format!("sam-test-append-{}", process::id()));
+        let _ = remove_dir_all(&dir);
+
+        let mut tera = Tera::default();
+        tera.add_raw_templates([
+            (PlainTextQuote.as_ref(), "> {{ text }}\n"),
+            (AppendHeader.as_ref(), "# {{ channel_name }}\n"),
+        ])?;
+        let message = |text: &str| {
+            let mut context = Context::new();
+            context.insert("channel_name", "general");
+            context.insert("text", text);
+            Client {
+                state: Retrieved {
+                    quote: false,
+                    thread: false,
+                    append_to: Some(format!("{}/{{{{ channel_name }}}}.md", dir.display())),
+                    tera: tera.clone(),
+                    template: NamedTemplate::default(),
+                    context,
+                },
+            }
+        };
+
+        // The file is created with the header, and the messages are separated by a blank line.
+        let path = message("hello").append()?.unwrap();
+        assert_eq!(path, dir.join("general.md"));
+        message("world").append()?;
+        assert_eq!(read_to_string(&path)?, "# general\n\n> hello\n\n> world\n");
+
+        // An existing file without the trailing newline is separated by a blank line as well.
+        write(&path, "notes")?;
+        message("hello").append()?;
+        assert_eq!(read_to_string(&path)?, "notes\n\n> hello\n");
+
+        let mut without = message("hello");
+        without.state.append_to = None;
+        assert_eq!(without.append()?, None);
+
+        remove_dir_all(&dir)?;
+
+        if let Some(home) = var_os("HOME") {
+            assert_eq!(expand_home("~/notes.md"), PathBuf::from(home).join("notes.md"));
+        }
+        assert_eq!(expand_home("notes/~/a.md"), PathBuf::from("notes/~/a.md"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timezones_for() -> Result<()> {
+        let client = Client {
+            state: Initialized {
+                source: Source::Export(Export::open("tests/fixtures/export".as_ref())?),
+                http: HttpClient::new(),
+                cache: None,
+                quote: false,
+                thread: false,
+                reactions: false,
+                download_files: None,
+                append_to: None,
+                timezone: "Asia/Tokyo",
+                timezones: vec!["Asia/Tokyo", "Europe/Berlin", "America/Los_Angeles"],
+                workspace_timezones: HashMap::from([
+                    ("acme".to_string(), "Europe/Berlin".to_string()),
+                    ("acme-eng".to_string(), "UTC".to_string()),
+                ]),
+                locale: None,
+                tera: Tera::default(),
+                template: NamedTemplate::default(),
+            },
+        };
+        let timezones_for = |url: &str| -> Result<(String, Vec<String>)> {
+            let (timezone, timezones) = client.timezones_for(&url::Url::parse(url)?);
+            Ok((timezone.to_string(), timezones.into_iter().map(String::from).collect()))
+        };
+
+        // The one of the workspace comes first, followed by the others without the duplicate.
+        assert_eq!(
+            timezones_for("https://acme.slack.com/archives/C01234567/p1724261952503309")?,
+            (
+                "Europe/Berlin".to_string(),
+                ["Europe/Berlin", "Asia/Tokyo", "America/Los_Angeles"]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+        assert_eq!(
+            timezones_for(
+                "https://acme-eng.enterprise.slack.com/archives/C01234567/p1724261952503309"
+            )?
+            .1,
+            ["UTC", "Asia/Tokyo", "Europe/Berlin", "America/Los_Angeles"]
+        );
+
+        // The configured ones for the other workspaces.
+        assert_eq!(
+            timezones_for("https://other.slack.com/archives/C01234567/p1724261952503309")?,
+            (
+                "Asia/Tokyo".to_string(),
+                ["Asia/Tokyo", "Europe/Berlin", "America/Los_Angeles"]
+                    .map(String::from)
+                    .to_vec()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quote_zones() -> Result<()> {
+        let (sources, named) =
+            template::load(&Templates::default(), Preset::default(), None).await?;
+        let timezones = ["Asia/Tokyo", "Europe/Berlin", "America/Los_Angeles"];
+        let context = sample_context("Asia/Tokyo", &timezones, None, false)?;
+        let tera = template::setup_tera(&sources, "Asia/Tokyo", None)?;
+
+        let (rich_text, text) =
+            Client::<Retrieved>::preview(tera, named.get("quote_zones")?, context).render()?;
+        let expected =
+            "on 2024-08-22 02:39:12 (JST) (19:39:12 CEST on 2024-08-21, 10:39:12 PDT on \
+                        2024-08-21):";
+        assert!(text.contains(expected), "{text}");
+        assert!(rich_text.contains(expected), "{rich_text}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reactions() -> Result<()> {
+        let (sources, named) =
+            template::load(&Templates::default(), Preset::default(), None).await?;
+        let tera = template::setup_tera(&sources, "Asia/Tokyo", None)?;
+
+        for (reactions, expected) in [(true, true), (false, false)] {
+            let context = sample_context("Asia/Tokyo", &[], None, reactions)?;
+            let (rich_text, text) =
+                Client::<Retrieved>::preview(tera.clone(), named.get("quote")?, context)
+                    .render()?;
+            assert_eq!(text.contains("\n\n✅ ×5 👀 ×2"), expected, "{text}");
+            assert_eq!(rich_text.contains("<p>✅ ×5 👀 ×2</p>"), expected, "{rich_text}");
+        }
+
+        #[rustfmt::skip]
+        [
+            ("white_check_mark", "✅"),
+            ("+1::skin-tone-2",  "👍"),
+            ("party-parrot",     ":party-parrot:"),
+        ]
+        .into_iter()
+        .for_each(|(name, expected)| {
+            let reaction = Reaction::from(&slack::Reaction { name: name.to_string(), count: 1 });
+            assert_eq!(reaction.emoji, expected, "{name}");
+        });
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_files() -> Result<()> {
+        let (sources, named) =
+            template::load(&Templates::default(), Preset::default(), None).await?;
+        let tera = template::setup_tera(&sources, "Asia/Tokyo", None)?;
+        let render = |context| {
+            Client::<Retrieved>::preview(tera.clone(), named.get("quote")?, context).render()
+        };
+
+        // An image is shown as the thumbnail linked to Slack.
+        let (rich_text, text) = render(sample_context("Asia/Tokyo", &[], None, false)?)?;
+        assert!(
+            text.contains("\n\n- [![screenshot.png](https://files.slack.com/files-tmb/"),
+            "{text}"
+        );
+        assert!(rich_text.contains("<li><a href=\"https://xxx.slack.com/files/"), "{rich_text}");
+
+        // A downloaded file is linked to the local path.
+        let mut context = sample_context("Asia/Tokyo", &[], None, false)?;
+        let file = File {
+            name: "spec.pdf".to_string(),
+            mimetype: "application/pdf".to_string(),
+            size: 2048,
+            path: "/tmp/files/F01234567-spec.pdf".to_string(),
+            ..File::default()
         };
+        context.insert("files", &[file]);
+        let (rich_text, text) = render(context)?;
+        assert!(text.contains("- [spec.pdf](/tmp/files/F01234567-spec.pdf) ("), "{text}");
+        assert!(
+            rich_text.contains("<a href=\"/tmp/files/F01234567-spec.pdf\">spec.pdf</a>"),
+            "{rich_text}"
+        );
 
-        Ok((rich_text, text))
+        Ok(())
     }
 }
@@ -1,6 +1,13 @@
+use std::{collections::HashMap, path::Path};
+
 use tera::{Context, Tera};
 
-use crate::template::Templates;
+use crate::{
+    cache::Cache,
+    client::{TokenSource, Tokens},
+    slack::{Export, HttpClient},
+    template::{Locale, NamedTemplate, Preset, Templates},
+};
 
 /// A marker trait for the state of the client.
 ///
@@ -26,28 +33,100 @@ impl State for Retrieved {}
 
 /// Uninitialized state of the client, or the CLI arguments.
 pub struct Uninitialized<'state> {
-    /// Slack API token.
-    pub token: &'state str,
+    /// Where to read the Slack API token, used when no workspace-specific token is configured.
+    pub token: TokenSource<'state>,
+    /// Look up the workspace-specific tokens in the OS secret service.
+    pub keyring: bool,
+    /// Slack API tokens configured in the configuration file, keyed by the workspace.
+    pub workspace_tokens: HashMap<String, String>,
+    /// Path to the Slack workspace export to use instead of the Web API.
+    pub export: Option<&'state Path>,
+    /// Do not use the on-disk cache at all.
+    pub no_cache: bool,
+    /// Ignore the cached data, but still update the cache.
+    pub refresh: bool,
     /// Include the message body as a quote.
     pub quote: bool,
-    /// The IANA time zone database identifiers to use for the timestamp.
+    /// Include the whole thread the message belongs to.
+    pub thread: bool,
+    /// Show a line of the reactions and the number of the replies under the quote.
+    pub reactions: bool,
+    /// The directory to download the files shared with the messages to, if any.
+    pub download_files: Option<&'state Path>,
+    /// The path template of the file to append the messages to.
+    pub append_to: Option<&'state str>,
+    /// The IANA time zone database identifier to use for the date variables.
     pub timezone: &'state str,
+    /// The IANA time zone database identifiers to use for `zones`.
+    pub timezones: Vec<&'state str>,
+    /// The IANA time zone database identifiers configured for the workspaces, which take
+    /// precedence over `timezone`, keyed by the workspace.
+    pub workspace_timezones: HashMap<String, String>,
+    /// The locale of the names of the months and the weekdays, and the timestamp format, if any.
+    pub locale: Option<Locale>,
+    /// The preset of the plain text templates, used for the ones not given in `templates`.
+    pub preset: Preset,
     pub templates: Templates,
+    /// The directory of the named templates, if any.
+    pub templates_dir: Option<&'state Path>,
+    /// The name of the template to render the messages with. Defaults to the built-in template
+    /// according to `quote` and `thread`.
+    pub template: Option<&'state str>,
 }
 
 /// Initialized state of the client.
 pub struct Initialized<'state> {
-    /// Slack API token.
-    pub token: &'state str,
+    /// Where to retrieve the messages from.
+    pub source: Source,
+
+    /// The HTTP client for the Web API, which is shared to reuse the connections.
+    pub http: HttpClient,
+
+    /// The on-disk cache of the data retrieved from the Slack API, if enabled.
+    pub cache: Option<Cache>,
 
     /// Include the message body as a quote.
     pub quote: bool,
 
-    /// The IANA time zone database identifiers to use for the timestamp.
+    /// Include the whole thread the message belongs to.
+    pub thread: bool,
+
+    /// Show a line of the reactions and the number of the replies under the quote.
+    pub reactions: bool,
+
+    /// The directory to download the files shared with the messages to, if any.
+    pub download_files: Option<&'state Path>,
+
+    /// The path template of the file to append the messages to.
+    pub append_to: Option<&'state str>,
+
+    /// The IANA time zone database identifier to use for the date variables.
     pub timezone: &'state str,
 
+    /// The IANA time zone database identifiers to use for `zones`.
+    pub timezones: Vec<&'state str>,
+
+    /// The IANA time zone database identifiers configured for the workspaces, which take
+    /// precedence over `timezone`, keyed by the workspace.
+    pub workspace_timezones: HashMap<String, String>,
+
+    /// The locale of the names of the months and the weekdays, and the timestamp format, if any.
+    pub locale: Option<Locale>,
+
     /// The Tera template engine with the templates set up.
     pub tera: Tera,
+
+    /// The template to render the messages with.
+    pub template: NamedTemplate,
+}
+
+/// The source of the Slack messages.
+pub enum Source {
+    /// The Slack Web API, with the API tokens.
+    Api(Tokens),
+
+    /// A Slack workspace export, either a directory or a ZIP archive.
+    Export(Export),
 }
 
 /// Retrieved state of the client.
@@ -55,9 +134,18 @@ pub struct Retrieved {
     /// Include the message body as a quote.
     pub quote: bool,
 
+    /// Include the whole thread the message belongs to.
+    pub thread: bool,
+
+    /// The path template of the file to append the message to.
+    pub append_to: Option<String>,
+
     /// The tera template engine.
     pub tera: Tera,
 
+    /// The template to render the message with.
+    pub template: NamedTemplate,
+
     /// The Slack message data as a template context.
     pub context: Context,
 }
@@ -0,0 +1,237 @@
+use std::{collections::HashMap, env::var, path::Path, process::Stdio};
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "keyring")]
+use keyring::Entry;
+use tokio::{fs::read_to_string, process::Command};
+use url::Url;
+use zeroize::Zeroizing;
+
+/// The account name of the default token in the OS secret service.
+const KEYRING_DEFAULT_ACCOUNT: &str = "default";
+
+/// The Slack API tokens, for each workspace. The tokens are zeroized when dropped.
+///
+/// A workspace is identified by the first label of the host of the URL, e.g. `acme` for
+/// `acme.slack.com`, or `acme-eng` for `acme-eng.enterprise.slack.com`.
+pub struct Tokens {
+    /// The token to use when no workspace-specific token is configured.
+    pub default: Option<Zeroizing<String>>,
+
+    /// The tokens configured in the configuration file, keyed by the workspace.
+    pub workspaces: HashMap<String, Zeroizing<String>>,
+
+    /// Look up the workspace-specific tokens in the OS secret service.
+    pub keyring: bool,
+}
+
+/// Where to read the default Slack API token from.
+pub enum TokenSource<'a> {
+    /// No default token.
+    None,
+
+    /// The token itself, given via `--token` or `SLACK_TOKEN`.
+    Plain(&'a str),
+
+    /// A file which contains the token.
+    File(&'a Path),
+
+    /// A shell command which prints the token to the standard output.
+    Command(&'a str),
+
+    /// The OS secret service, e.g. Secret Service on Linux, or Keychain on macOS.
+    Keyring,
+}
+
+impl TokenSource<'_> {
+    /// Read the token from the source. The token itself never appears in the error messages.
+    pub async fn read(&self) -> Result<Option<Zeroizing<String>>> {
+        let token = match self {
+            TokenSource::None => return Ok(None),
+            TokenSource::Plain(token) => Zeroizing::new(token.trim().to_string()),
+            TokenSource::File(path) => Zeroizing::new(
+                read_to_string(path)
+                    .await
+                    .map_err(|why| anyhow!("failed to read {}: {why}", path.display()))?
+                    .trim()
+                    .to_string(),
+            ),
+            TokenSource::Command(command) => {
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .stdin(Stdio::inherit())
+                    .stderr(Stdio::inherit())
+                    .output()
+                    .await
+                    .map_err(|why| anyhow!("failed to run the token command: {why}"))?;
+                let stdout = Zeroizing::new(output.stdout);
+                if !output.status.success() {
+                    return Err(anyhow!("the token command exited with {}", output.status));
+                }
+                Zeroizing::new(
+                    std::str::from_utf8(&stdout)
+                        .map_err(|_| anyhow!("the token command printed a non UTF-8 text"))?
+                        .trim()
+                        .to_string(),
+                )
+            }
+            TokenSource::Keyring => return read_keyring(KEYRING_DEFAULT_ACCOUNT),
+        };
+
+        Ok(Some(token).filter(|token| !token.is_empty()))
+    }
+}
+
+impl Tokens {
+    /// Pick the token for the workspace the given URL belongs to, in the following order:
+    ///
+    /// 1. `SLACK_TOKEN_<WORKSPACE>` environment variable, e.g. `SLACK_TOKEN_ACME_ENG`.
+    /// 2. `token` in the `[workspace.<workspace>]` table of the configuration file.
+    /// 3. The entry for the workspace in the OS secret service, if enabled.
+    /// 4. The default token.
+    pub fn for_url(&self, url: &Url) -> Result<Zeroizing<String>> {
+        let workspace = workspace(url)?;
+        let var_name = format!(
+            "SLACK_TOKEN_{}",
+            workspace
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+                .collect::<String>()
+        );
+
+        if let Some(token) = var(&var_name).ok().filter(|token| !token.is_empty()) {
+            return Ok(Zeroizing::new(token));
+        }
+        if let Some(token) = self.workspaces.get(workspace) {
+            return Ok(token.clone());
+        }
+        if self.keyring {
+            if let Some(token) = read_keyring(workspace)? {
+                return Ok(token);
+            }
+        }
+
+        self.default.clone().ok_or_else(|| {
+            anyhow!(
+                "no Slack API token is configured for workspace '{workspace}'. Set {var_name} or \
+                 SLACK_TOKEN, use --token-file, --token-command, or --token-keyring, or add \
+                 `token` to the [workspace.{workspace}] table of the configuration file"
+            )
+        })
+    }
+}
+
+// Read the token for the given account from the OS secret service, with the name of this program
+// as the service name.
+#[cfg(feature = "keyring")]
+fn read_keyring(account: &str) -> Result<Option<Zeroizing<String>>> {
+    match Entry::new(env!("CARGO_BIN_NAME"), account)?.get_password() {
+        Ok(token) => Ok(Some(Zeroizing::new(token))),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(why) => {
+            Err(anyhow!("failed to read the token for '{account}' from the keyring: {why}"))
+        }
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+fn read_keyring(account: &str) -> Result<Option<Zeroizing<String>>> {
+    Err(anyhow!(
+        "failed to read the token for '{account}' from the keyring: this build of {} does not \
+         support the keyring. Rebuild it with the `keyring` feature enabled",
+        env!("CARGO_BIN_NAME")
+    ))
+}
+
+/// Get the workspace the given URL belongs to.
+pub fn workspace(url: &Url) -> Result<&str> {
+    url.host_str()
+        .filter(|host| host.ends_with(".slack.com"))
+        .and_then(|host| host.split('.').next())
+        .ok_or_else(|| anyhow!("{url} is not a Slack URL"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env::set_var};
+
+    use anyhow::Result;
+    use url::Url;
+    use zeroize::Zeroizing;
+
+    use crate::client::{tokens::workspace, TokenSource, Tokens};
+
+    #[test]
+    fn test_workspace() -> Result<()> {
+        #[rustfmt::skip]
+        let cases = [
+            ("https://acme.slack.com/archives/C0123/p1724743664325609",                Some("acme")),
+            ("https://acme-eng.enterprise.slack.com/archives/C0123/p1724743664325609", Some("acme-eng")),
+            ("https://slack.com/archives/C0123/p1724743664325609",                     None),
+            ("https://example.com/archives/C0123/p1724743664325609",                   None),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(workspace(&Url::parse(url)?).ok(), expected, "{url}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_url() -> Result<()> {
+        // The workspaces are unique to this test, as the environment is shared by the tests.
+        set_var("SLACK_TOKEN_SAM_TEST_ENV", "env");
+        set_var("SLACK_TOKEN_SAM_TEST_EMPTY", "");
+        let tokens = Tokens {
+            default: Some(Zeroizing::new("default".to_string())),
+            workspaces: HashMap::from([
+                ("sam-test-env".to_string(), Zeroizing::new("config".to_string())),
+                ("sam-test-empty".to_string(), Zeroizing::new("config".to_string())),
+                ("sam-test-config".to_string(), Zeroizing::new("config".to_string())),
+            ]),
+            keyring: false,
+        };
+
+        #[rustfmt::skip]
+        let cases = [
+            ("sam-test-env",     "env"),
+            ("sam-test-empty",   "config"),
+            ("sam-test-config",  "config"),
+            ("sam-test-default", "default"),
+        ];
+
+        for (workspace, expected) in cases {
+            let url = Url::parse(&format!("https://{workspace}.slack.com/archives/C0123"))?;
+            assert_eq!(tokens.for_url(&url)?.as_str(), expected, "{workspace}");
+        }
+
+        let tokens = Tokens {
+            default: None,
+            workspaces: HashMap::new(),
+            keyring: false,
+        };
+        let url = Url::parse("https://sam-test-none.slack.com/archives/C0123")?;
+        assert!(tokens.for_url(&url).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read() -> Result<()> {
+        let read = |source: TokenSource<'static>| async move {
+            source.read().await.map(|token| token.map(|token| token.to_string()))
+        };
+
+        assert_eq!(read(TokenSource::None).await?, None);
+        assert_eq!(read(TokenSource::Plain(" xoxp \n")).await?.as_deref(), Some("xoxp"));
+        assert_eq!(read(TokenSource::Plain("")).await?, None);
+        assert_eq!(read(TokenSource::Command("echo ' xoxp'")).await?.as_deref(), Some("xoxp"));
+        assert!(read(TokenSource::Command("exit 1")).await.is_err());
+        #[cfg(not(feature = "keyring"))]
+        assert!(read(TokenSource::Keyring).await.is_err());
+
+        Ok(())
+    }
+}
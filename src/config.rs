@@ -0,0 +1,160 @@
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{
+    template::{Locale, Preset, Templates},
+    xdg::config_dir,
+};
+
+/// The configuration file, which holds the default settings at the top level, and the named
+/// profiles in the `[profile.<name>]` tables.
+#[derive(Deserialize, Default)]
+pub struct Config {
+    /// The default settings.
+    #[serde(flatten)]
+    pub defaults: Settings,
+
+    /// The named profiles, which override the default settings.
+    #[serde(default)]
+    pub profile: HashMap<String, Settings>,
+
+    /// The settings for each Slack workspace, keyed by the workspace, e.g. `acme` for
+    /// `acme.slack.com`.
+    #[serde(default)]
+    pub workspace: HashMap<String, Workspace>,
+}
+
+/// The settings for a Slack workspace.
+#[derive(Deserialize, Default)]
+pub struct Workspace {
+    /// Slack API token for the workspace.
+    pub token: Option<String>,
+
+    /// The IANA time zone database identifier to use for the messages in the workspace.
+    pub timezone: Option<String>,
+}
+
+/// The settings which can be configured in the configuration file.
+#[derive(Deserialize, Default)]
+pub struct Settings {
+    /// The IANA time zone database identifiers to use for the timestamp, either one or a list.
+    pub timezone: Option<OneOrMany>,
+
+    /// The locale of the names of the months and the weekdays, and the timestamp format.
+    pub locale: Option<Locale>,
+
+    /// Include the message body as a quote.
+    pub quote: Option<bool>,
+
+    /// Show a line of the reactions and the number of the replies under the quote.
+    pub reactions: Option<bool>,
+
+    /// Path to the directory to download the files shared with the messages to.
+    pub download_files: Option<PathBuf>,
+
+    /// The preset of the plain text templates.
+    pub preset: Option<Preset>,
+
+    /// Name of the template to render the messages with.
+    pub template: Option<String>,
+
+    /// Path to the template file or a string, for each template.
+    #[serde(default)]
+    pub templates: Templates,
+}
+
+/// Either a single value or a list of values, e.g. `"Asia/Tokyo"` or
+/// `["Asia/Tokyo", "Europe/Berlin"]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<OneOrMany> for Vec<String> {
+    fn from(value: OneOrMany) -> Self {
+        match value {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file at the given path. If no path is given, load `config.toml` in
+    /// the XDG config directory if it exists, or use the empty configuration otherwise.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match config_dir().map(|dir| dir.join("config.toml")) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Self::default()),
+            },
+        };
+
+        let content = read_to_string(&path)
+            .map_err(|why| anyhow!("failed to read {}: {why}", path.display()))?;
+        // Do not show the source snippet of the error, as it might contain a token.
+        toml::from_str(&content).map_err(|why| {
+            let line = why
+                .span()
+                .map_or(0, |span| content[..span.start].matches('\n').count() + 1);
+            anyhow!("failed to parse {} at line {line}: {}", path.display(), why.message())
+        })
+    }
+
+    /// Get the settings of the given profile, falling back to the default settings. If no
+    /// profile is given, just return the default settings.
+    pub fn settings(&mut self, profile: Option<&str>) -> Result<Settings> {
+        let defaults = std::mem::take(&mut self.defaults);
+        let Some(name) = profile else {
+            return Ok(defaults);
+        };
+
+        let profile = self
+            .profile
+            .remove(name)
+            .ok_or_else(|| anyhow!("profile '{name}' is not defined in the configuration file"))?;
+
+        Ok(profile.or(defaults))
+    }
+
+    /// Get the Slack API tokens configured for the workspaces, keyed by the workspace.
+    pub fn workspace_tokens(&self) -> HashMap<String, String> {
+        self.workspace
+            .iter()
+            .filter_map(|(name, workspace)| Some((name.clone(), workspace.token.clone()?)))
+            .collect()
+    }
+
+    /// Get the time zones configured for the workspaces, keyed by the workspace.
+    pub fn workspace_timezones(&self) -> HashMap<String, String> {
+        self.workspace
+            .iter()
+            .filter_map(|(name, workspace)| Some((name.clone(), workspace.timezone.clone()?)))
+            .collect()
+    }
+}
+
+impl Settings {
+    /// Fill the settings not set in `self` with the `fallback`.
+    pub fn or(self, fallback: Settings) -> Settings {
+        Settings {
+            timezone: self.timezone.or(fallback.timezone),
+            locale: self.locale.or(fallback.locale),
+            quote: self.quote.or(fallback.quote),
+            reactions: self.reactions.or(fallback.reactions),
+            download_files: self.download_files.or(fallback.download_files),
+            preset: self.preset.or(fallback.preset),
+            template: self.template.or(fallback.template),
+            templates: self.templates.or(fallback.templates),
+        }
+    }
+}
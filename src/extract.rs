@@ -0,0 +1,98 @@
+use std::{ops::Range, sync::LazyLock};
+
+use regex::Regex;
+
+/// The pattern of a Slack message URL, i.e. a permalink.
+static SLACK_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"https://[a-zA-Z0-9.-]+\.slack\.com/archives/[A-Z0-9]+/p\d+(?:\?[^\s<>()\[\]]*)?")
+        .unwrap()
+});
+
+/// A Slack message URL found in a text.
+#[derive(Debug, PartialEq)]
+pub struct Found<'text> {
+    /// The byte range of the URL in the text, including the surrounding angle brackets if any.
+    pub range: Range<usize>,
+
+    /// The URL itself.
+    pub url: &'text str,
+}
+
+/// Find the bare Slack message URLs in the given text.
+///
+/// URLs which are already the target of a link, i.e. `[label](url)` or `<a href="url">`, are
+/// skipped. Trailing punctuation, which is likely a part of the surrounding sentence, is not
+/// included in the URL.
+pub fn find_urls(text: &str) -> Vec<Found<'_>> {
+    SLACK_URL
+        .find_iter(text)
+        .filter_map(|m| {
+            let url = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?', '\'', '"']);
+            let (start, end) = (m.start(), m.start() + url.len());
+            let before = &text[..start];
+
+            if before.ends_with("](") || before.ends_with("href=\"") {
+                return None;
+            }
+
+            let range = if before.ends_with('<') && text[end..].starts_with('>') {
+                start - 1..end + 1
+            } else {
+                start..end
+            };
+
+            Some(Found { range, url })
+        })
+        .collect()
+}
+
+/// Replace the found URLs in the text with the given replacements, which are in the same order as
+/// `found`. The surrounding text is kept as is.
+pub fn replace(text: &str, found: &[Found], replacements: &[String]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for (found, replacement) in found.iter().zip(replacements) {
+        result.push_str(&text[last..found.range.start]);
+        result.push_str(replacement.trim());
+        last = found.range.end;
+    }
+    result.push_str(&text[last..]);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extract::{find_urls, replace};
+
+    const URL: &str = "https://xxx.slack.com/archives/C01234567/p1724743664325609";
+
+    #[test]
+    fn test_find_urls() {
+        let text = format!(
+            "- decided in {URL}.\n- see <{URL}?thread_ts=1724743600.000100&cid=C01234567>\n- [already linked]({URL})"
+        );
+        let found = find_urls(&text);
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].url, URL);
+        assert_eq!(&text[found[0].range.clone()], URL);
+        assert_eq!(found[1].url, format!("{URL}?thread_ts=1724743600.000100&cid=C01234567"));
+        assert!(text[found[1].range.clone()].starts_with('<'));
+        assert!(text[found[1].range.clone()].ends_with('>'));
+    }
+
+    #[test]
+    fn test_replace() {
+        let text = format!("Decided in {URL}, see <{URL}> for details.");
+        let found = find_urls(&text);
+        let replacements =
+            vec!["[Slack#general](a)".to_string(), "[Slack#random](b)\n".to_string()];
+
+        assert_eq!(
+            replace(&text, &found, &replacements),
+            "Decided in [Slack#general](a), see [Slack#random](b) for details."
+        );
+    }
+}
@@ -1,51 +1,384 @@
+use std::{fs::read_to_string, path::Path, time::Duration};
+
 use anyhow::{anyhow, Result};
-use arboard::Clipboard;
 use clap::Parser;
+use futures::future::try_join_all;
+use serde_json::{json, Value};
+use strum::VariantArray;
+use tera::Context;
+use tokio::time::sleep;
 use url::Url;
 
-use crate::{args::Args, client::Client};
+use crate::{
+    args::{split_urls, Args, CacheCommand, Command, TemplateCommand},
+    cache::Cache,
+    client::{
+        sample_context,
+        state::{Initialized, Retrieved},
+        to_html, Client, SYSTEM_TIMEZONE,
+    },
+    extract::{find_urls, replace},
+    output::{Clipboard, Format},
+    template::{ContextKey, Finding, NamedTemplates, Severity, TemplateSource},
+};
 
 mod args;
+mod cache;
 mod client;
+mod config;
+mod extract;
+mod output;
+mod slack;
 mod template;
+mod xdg;
+
+/// How often to check the templates for changes with `template preview --watch`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    if let Some(command) = args.command.take() {
+        return run(command, &mut args).await;
+    }
+
+    args.apply_config()
+        .map_err(|why| anyhow!("failed to load configuration: {why}"))?;
+
     let client = Client::from((&args).into())
         .await
         .map_err(|why| anyhow!("failed to initialize client: {why}"))?;
 
-    let mut clipboard =
-        Clipboard::new().map_err(|why| anyhow!("failed to access system clipboard: {why}"))?;
+    let mut clipboard = Clipboard::default();
+
+    let (rich_text, text) = if args.extract {
+        let input = match args.read_input()? {
+            Some(input) => input,
+            None => clipboard.get_text()?,
+        };
+
+        let found = find_urls(&input);
+        let urls = found
+            .iter()
+            .map(|found| parse_url(found.url))
+            .collect::<Result<Vec<_>>>()?;
+        let messages = retrieve_all(&client, &urls).await?;
+        append_all(&messages)?;
+        let (rich_texts, texts) = messages
+            .iter()
+            .map(|message| message.render())
+            .collect::<Result<Vec<_>>>()
+            .map_err(|why| anyhow!("failed to render message: {why}"))?
+            .into_iter()
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+
+        (to_html(&replace(&input, &found, &rich_texts)), replace(&input, &found, &texts))
+    } else {
+        let mut texts = args.collect_urls()?;
+        if texts.is_empty() {
+            texts.extend(split_urls(&clipboard.get_text()?));
+        }
+
+        let urls = texts.iter().map(|text| parse_url(text)).collect::<Result<Vec<_>>>()?;
+        if urls.is_empty() {
+            return Err(anyhow!("no Slack URL to process"));
+        }
+
+        let messages = retrieve_all(&client, &urls).await?;
+        append_all(&messages)?;
+
+        if let Some(structured) = args.structured() {
+            let text = structured
+                .serialize(messages.iter().map(|message| message.to_json()).collect())
+                .map_err(|why| anyhow!("failed to serialize message: {why}"))?;
+            return args.output.write(&mut clipboard, Format::Plain, "", &text);
+        }
+
+        Client::<Retrieved>::render_all(&messages)
+            .map_err(|why| anyhow!("failed to render message: {why}"))?
+    };
+
+    args.output.write(&mut clipboard, args.format, &rich_text, &text)
+}
+
+async fn run(command: Command, args: &mut Args) -> Result<()> {
+    match command {
+        Command::Cache { command } => {
+            let cache = Cache::open(false)?;
+            match command {
+                CacheCommand::Clear => {
+                    cache.clear()?;
+                    println!("Cleared {}", cache.root().display());
+                }
+                CacheCommand::Stats => {
+                    println!("{}", cache.root().display());
+                    for (kind, count, size) in cache.stats()? {
+                        println!("{:10} {count:>6} entries {size:>10} bytes", kind.as_ref());
+                    }
+                }
+            }
+        }
+        Command::Vars { url, json } => {
+            args.apply_config()
+                .map_err(|why| anyhow!("failed to load configuration: {why}"))?;
+            list_vars(args, url.as_deref(), json).await?;
+        }
+        Command::Template { command } => {
+            args.apply_config()
+                .map_err(|why| anyhow!("failed to load configuration: {why}"))?;
+            match command {
+                TemplateCommand::Check => check_templates(args).await?,
+                TemplateCommand::Preview { name, fixture, watch } => {
+                    preview_template(args, &name, fixture.as_deref(), watch).await?
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Print all the template variables with the examples, taken from the message the given URL points
+// to, or the current time and the sample values.
+async fn list_vars(args: &mut Args, url: Option<&str>, json: bool) -> Result<()> {
+    let examples = match url {
+        Some(url) => {
+            // Include the message body in the examples.
+            args.quote = true;
+            let client = Client::from((&*args).into())
+                .await
+                .map_err(|why| anyhow!("failed to initialize client: {why}"))?;
+            let context = client
+                .retrieve(&parse_url(url)?)
+                .await
+                .map_err(|why| anyhow!("failed to retrieve message from Slack: {why}"))?
+                .to_json();
+            ContextKey::VARIANTS
+                .iter()
+                .map(|key| context.get(key.as_ref()).cloned().unwrap_or(Value::Null))
+                .collect::<Vec<_>>()
+        }
+        None => {
+            let now = jiff::Timestamp::now().in_tz(args.timezone())?;
+            ContextKey::VARIANTS
+                .iter()
+                .map(|key| match key {
+                    // The detected one rather than the one in the documentation.
+                    ContextKey::SystemTz => Value::from(SYSTEM_TIMEZONE.as_str()),
+                    key => Value::from(key.example(&now, args.locale)),
+                })
+                .collect()
+        }
+    };
+
+    if json {
+        let vars = ContextKey::VARIANTS
+            .iter()
+            .zip(examples)
+            .map(|(key, example)| {
+                json!({
+                    "name": key.as_ref(),
+                    "format": key.format(),
+                    "description": key.description(),
+                    "example": example,
+                })
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&vars)?);
+        return Ok(());
+    }
+
+    println!("{:20} {:24} {:36} Description", "Variable", "Format", "Example");
+    for (key, example) in ContextKey::VARIANTS.iter().zip(examples) {
+        let example = match example {
+            Value::String(example) => example,
+            Value::Null => "-".to_string(),
+            example => example.to_string(),
+        };
+        println!(
+            "{:20} {:24} {:36} {}",
+            key.as_ref(),
+            key.format().unwrap_or("-"),
+            example.chars().take(36).collect::<String>(),
+            key.description()
+        );
+    }
+
+    Ok(())
+}
+
+// Load all the configured templates.
+async fn load_templates(args: &Args) -> Result<(Vec<TemplateSource>, NamedTemplates)> {
+    template::load(&args.templates, args.preset.unwrap_or_default(), args.templates_dir.as_deref())
+        .await
+        .map_err(|why| anyhow!("failed to load templates: {why}"))
+}
+
+// Check all the configured templates, and print the findings.
+async fn check_templates(args: &Args) -> Result<()> {
+    let (sources, named) = load_templates(args).await?;
+
+    let mut findings = template::check(&sources);
+    if let Some(Err(why)) = args.template.as_deref().map(|name| named.get(name)) {
+        findings.push(Finding {
+            severity: Severity::Error,
+            origin: "--template".to_string(),
+            line: None,
+            message: why.to_string(),
+        });
+    }
+
+    findings.iter().for_each(|finding| eprintln!("{finding}"));
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(anyhow!("{errors} error(s) found in the templates"));
+    }
+
+    let checked = sources.iter().filter(|source| !source.name.starts_with("builtin/"));
+    println!("Checked {} templates", checked.count());
+
+    Ok(())
+}
+
+// Render the template with the given name against the sample message or the fixture, and print
+// the result. With `watch`, render again periodically, and print the result whenever it changes.
+async fn preview_template(
+    args: &Args,
+    name: &str,
+    fixture: Option<&Path>,
+    watch: bool,
+) -> Result<()> {
+    let mut last = None;
+
+    loop {
+        let result = preview(args, name, fixture).await;
+        if !watch {
+            return result.map(|output| println!("{output}"));
+        }
+
+        // Keep watching even if the template is broken for now.
+        let output = result.unwrap_or_else(|why| format!("Error: {why}"));
+        if last.as_ref() != Some(&output) {
+            println!("{output}\n");
+            last = Some(output);
+        }
+
+        sleep(WATCH_INTERVAL).await;
+    }
+}
+
+// Render the template with the given name against the sample message, with the values in the
+// fixture if any, into the plain text followed by the rich text.
+async fn preview(args: &Args, name: &str, fixture: Option<&Path>) -> Result<String> {
+    let (sources, named) = load_templates(args).await?;
+
+    let mut context =
+        sample_context(args.timezone(), &args.timezones(), args.locale, args.reactions)?;
+    if let Some(path) = fixture {
+        context.extend(read_fixture(path)?);
+    }
+
+    let message = Client::<Retrieved>::preview(
+        template::setup_tera(&sources, args.timezone(), args.locale)?,
+        named.get(name)?,
+        context,
+    );
+    let (rich_text, text) = message
+        .render()
+        .map_err(|why| anyhow!("failed to render template: {why}"))?;
+
+    Ok(format!("{}\n\n{}", text.trim(), rich_text.trim()))
+}
 
-    let text = match args.url {
-        Some(ref url) => url,
-        None => &clipboard
-            .get_text()
-            .map_err(|why| anyhow!("failed to get text from clipboard: {why}"))?,
+// Read the template variables in the given JSON file. The output of `--json` is also accepted,
+// where the first message is used.
+fn read_fixture(path: &Path) -> Result<Context> {
+    let content =
+        read_to_string(path).map_err(|why| anyhow!("failed to read {}: {why}", path.display()))?;
+    let value: Value = serde_json::from_str(&content)
+        .map_err(|why| anyhow!("failed to parse {}: {why}", path.display()))?;
+
+    let value = match (value.get("schema_version"), value.get("messages")) {
+        (Some(_), Some(messages)) => messages
+            .get(0)
+            .cloned()
+            .ok_or_else(|| anyhow!("no message in {}", path.display()))?,
+        _ => value,
     };
 
-    let url = Url::parse(text).map_err(|why| {
+    Context::from_value(value).map_err(|why| anyhow!("invalid fixture {}: {why}", path.display()))
+}
+
+fn parse_url(text: &str) -> Result<Url> {
+    Url::parse(text).map_err(|why| {
         anyhow!(
             "The provided text '{}...' is not a valid URL: {why}",
             text.chars().take(40).collect::<String>().trim()
         )
-    })?;
+    })
+}
 
-    let message = client
-        .retrieve(&url)
+async fn retrieve_all(
+    client: &Client<Initialized<'_>>,
+    urls: &[Url],
+) -> Result<Vec<Client<Retrieved>>> {
+    try_join_all(urls.iter().map(|url| client.retrieve(url)))
         .await
-        .map_err(|why| anyhow!("failed to retrieve message from Slack: {why}"))?;
+        .map_err(|why| anyhow!("failed to retrieve message from Slack: {why}"))
+}
 
-    let (rich_text, text) = message
-        .render()
-        .map_err(|why| anyhow!("failed to render message: {why}"))?;
+fn append_all(messages: &[Client<Retrieved>]) -> Result<()> {
+    messages.iter().try_for_each(|message| {
+        message
+            .append()
+            .map(|_| ())
+            .map_err(|why| anyhow!("failed to append message: {why}"))
+    })
+}
 
-    match clipboard.set_html(rich_text.trim(), Some(text.trim())) {
-        Ok(_) => println!("{text}"),
-        Err(why) => println!("{why}"),
-    }
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs::write, process};
 
-    Ok(())
+    use anyhow::Result;
+    use clap::Parser;
+
+    use crate::{args::Args, preview};
+
+    #[tokio::test]
+    async fn test_preview() -> Result<()> {
+        let args = Args::try_parse_from(["sam", "--timezone", "UTC"])?;
+
+        // The sample message only.
+        let output = preview(&args, "link", None).await?;
+        assert!(output.starts_with(
+            "[Slack#general](https://xxx.slack.com/archives/C01234567/p1724261952503309)\n\n"
+        ));
+        assert!(output.ends_with("</a>"));
+
+        // The values in the fixture replace the sample ones.
+        let fixture = temp_dir().join(format!("sam-test-preview-{}.json", process::id()));
+        write(&fixture, r#"{ "user_name": "finn", "text": ["Ship it"], "files": [] }"#)?;
+        let output = preview(&args, "quote", Some(&fixture)).await?;
+        assert!(output.starts_with("From **@finn** in [Slack#general]("));
+        assert!(output.contains("\n\n> Ship it\n\n<p>From <strong>@finn</strong>"));
+
+        // The first message of the output of `--json`.
+        write(
+            &fixture,
+            r#"{ "schema_version": 1, "messages": [{ "channel_name": "random" }, {}] }"#,
+        )?;
+        let output = preview(&args, "link", Some(&fixture)).await?;
+        assert!(output.starts_with("[Slack#random]("));
+
+        write(&fixture, r#"{ "schema_version": 1, "messages": [] }"#)?;
+        assert!(preview(&args, "link", Some(&fixture)).await.is_err());
+        assert!(preview(&args, "unknown", None).await.is_err());
+
+        Ok(())
+    }
 }
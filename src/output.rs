@@ -0,0 +1,235 @@
+use std::{fs::write, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+/// The version of the schema of the structured output. Incremented when the existing fields are
+/// changed or removed, but not when new ones are added.
+const SCHEMA_VERSION: u32 = 1;
+
+/// The system clipboard. It is accessed lazily, so that `sam` works without a display server as
+/// long as the clipboard is not actually used. Building without the `clipboard` feature drops the
+/// dependency on the clipboard completely.
+#[derive(Default)]
+pub struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    inner: Option<arboard::Clipboard>,
+}
+
+/// Where to write the result.
+#[derive(Debug, Clone)]
+pub enum Output {
+    /// The standard output.
+    Stdout,
+
+    /// The system clipboard.
+    Clipboard,
+
+    /// A file, which is overwritten.
+    File(PathBuf),
+
+    /// Both the standard output and the system clipboard.
+    Both,
+}
+
+/// The format of the result to write.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// The plain text.
+    Plain,
+
+    /// The rich text, i.e. HTML.
+    Html,
+
+    /// Both the plain text and the rich text. The clipboard holds them at once, while the
+    /// standard output and the file get the plain text only.
+    Both,
+}
+
+/// The format to write the template contexts of the messages in, instead of the rendered texts.
+#[derive(Debug, Clone, Copy)]
+pub enum Structured {
+    /// JSON, pretty-printed.
+    Json,
+
+    /// YAML.
+    Yaml,
+}
+
+/// The structured output.
+#[derive(Serialize)]
+struct Document {
+    /// The version of the schema.
+    schema_version: u32,
+
+    /// The template contexts of the messages, in the given order.
+    messages: Vec<Value>,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Output::Stdout),
+            "clipboard" => Ok(Output::Clipboard),
+            "both" => Ok(Output::Both),
+            _ => match s.strip_prefix("file:") {
+                Some(path) if !path.is_empty() => Ok(Output::File(PathBuf::from(path))),
+                _ => Err(format!("expected stdout, clipboard, file:<PATH>, or both, got '{s}'")),
+            },
+        }
+    }
+}
+
+impl Output {
+    /// Write the rendered rich text and plain text in the given format.
+    pub fn write(
+        &self,
+        clipboard: &mut Clipboard,
+        format: Format,
+        rich_text: &str,
+        text: &str,
+    ) -> Result<()> {
+        let (rich_text, text) = (rich_text.trim(), text.trim());
+        let content = match format {
+            Format::Plain | Format::Both => text,
+            Format::Html => rich_text,
+        };
+
+        match self {
+            Output::Stdout => println!("{content}"),
+            Output::Clipboard => clipboard.set(format, rich_text, text)?,
+            Output::File(path) => write(path, format!("{content}\n"))
+                .map_err(|why| anyhow!("failed to write to {}: {why}", path.display()))?,
+            Output::Both => {
+                clipboard.set(format, rich_text, text)?;
+                println!("{content}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Structured {
+    /// Serialize the given template contexts of the messages along with the schema version.
+    pub fn serialize(self, messages: Vec<Value>) -> Result<String> {
+        let document = Document { schema_version: SCHEMA_VERSION, messages };
+        Ok(match self {
+            Structured::Json => serde_json::to_string_pretty(&document)?,
+            Structured::Yaml => serde_yaml::to_string(&document)?,
+        })
+    }
+}
+
+#[cfg(feature = "clipboard")]
+impl Clipboard {
+    /// Get the text in the clipboard.
+    pub fn get_text(&mut self) -> Result<String> {
+        self.get()?
+            .get_text()
+            .map_err(|why| anyhow!("failed to get text from clipboard: {why}"))
+    }
+
+    // Set the text in the clipboard in the given format.
+    fn set(&mut self, format: Format, rich_text: &str, text: &str) -> Result<()> {
+        let clipboard = self.get()?;
+        match format {
+            Format::Plain => clipboard.set_text(text),
+            Format::Html => clipboard.set_html(rich_text, None),
+            Format::Both => clipboard.set_html(rich_text, Some(text)),
+        }
+        .map_err(|why| anyhow!("failed to set text to clipboard: {why}"))
+    }
+
+    fn get(&mut self) -> Result<&mut arboard::Clipboard> {
+        if self.inner.is_none() {
+            self.inner = Some(
+                arboard::Clipboard::new()
+                    .map_err(|why| anyhow!("failed to access system clipboard: {why}"))?,
+            );
+        }
+        Ok(self.inner.as_mut().unwrap())
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+impl Clipboard {
+    /// Get the text in the clipboard.
+    pub fn get_text(&mut self) -> Result<String> {
+        Err(anyhow!("built without clipboard support. Pass the URLs as the arguments"))
+    }
+
+    // Set the text in the clipboard in the given format.
+    fn set(&mut self, _: Format, _: &str, _: &str) -> Result<()> {
+        Err(anyhow!(
+            "built without clipboard support. Use `--output stdout` or `--output file:<PATH>`"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs::read_to_string, process, str::FromStr};
+
+    use anyhow::Result;
+    use serde_json::json;
+
+    use crate::output::{Clipboard, Format, Output, Structured};
+
+    #[test]
+    fn test_output() -> Result<()> {
+        assert!(matches!(Output::from_str("stdout"), Ok(Output::Stdout)));
+        assert!(matches!(Output::from_str("clipboard"), Ok(Output::Clipboard)));
+        assert!(matches!(Output::from_str("both"), Ok(Output::Both)));
+        assert!(
+            matches!(Output::from_str("file:out.md"), Ok(Output::File(path)) if path.to_str() == Some("out.md"))
+        );
+        assert!(Output::from_str("file:").is_err());
+        assert!(Output::from_str("stderr").is_err());
+
+        // The file gets the plain text only, unless the rich text is asked for.
+        let path = temp_dir().join(format!("sam-test-output-{}.md", process::id()));
+        let output = Output::File(path.clone());
+        for (format, expected) in
+            [(Format::Both, "text\n"), (Format::Plain, "text\n"), (Format::Html, "<p>html</p>\n")]
+        {
+            output.write(&mut Clipboard::default(), format, " <p>html</p>\n", "text\n\n")?;
+            assert_eq!(read_to_string(&path)?, expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_structured() -> Result<()> {
+        let messages = vec![
+            json!({ "user_name": "jake", "text": ["Hello,", "world!"] }),
+            json!({ "user_name": "finn", "text": [] }),
+        ];
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(
+                &Structured::Json.serialize(messages.clone())?
+            )?,
+            json!({ "schema_version": 1, "messages": messages })
+        );
+        assert_eq!(
+            Structured::Yaml.serialize(messages)?,
+            "schema_version: 1
+messages:
+- text:
+  - Hello,
+  - world!
+  user_name: jake
+- text: []
+  user_name: finn
+"
+        );
+
+        Ok(())
+    }
+}
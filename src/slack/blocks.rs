@@ -0,0 +1,521 @@
+use std::{collections::BTreeSet, sync::LazyLock};
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::slack::{emoji, mrkdwn};
+
+/// A mention without the label in mrkdwn, e.g. `<@U0123>`, `<#C0123>`, or `<!subteam^S0123>`.
+static MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(?:[@#]|!subteam\^)([A-Z0-9]+)(?:\|)?>").unwrap());
+
+/// The content of a Slack message, i.e. the text and the layout of it, either from the Web API or
+/// from an export.
+///
+/// The blocks and the attachments are kept as JSON, so that an unknown or malformed one does not
+/// fail the whole message, but ends up with a placeholder at worst.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Content {
+    /// The text of the message in mrkdwn, which is also the fallback of the blocks.
+    pub text: String,
+
+    /// The [blocks](https://api.slack.com/reference/block-kit/blocks) of the message.
+    pub blocks: Vec<Value>,
+
+    /// The [legacy attachments](https://api.slack.com/reference/messaging/attachments) of the
+    /// message, including the link previews.
+    pub attachments: Vec<Value>,
+}
+
+impl Content {
+    /// Whether the message has any layout other than the rich text, i.e. the blocks other than
+    /// `rich_text`, or the attachments other than the link previews, which are usually posted by
+    /// bots.
+    pub fn has_layout(&self) -> bool {
+        self.blocks.iter().any(|block| kind(block) != "rich_text")
+            || self.attachments().next().is_some()
+    }
+
+    /// The IDs of the users, the user groups, and the channels mentioned in the layout, to look up
+    /// the names of them in advance.
+    pub fn mentions(&self) -> BTreeSet<String> {
+        let mut ids = BTreeSet::new();
+        collect_mentions(&Value::String(self.text.clone()), &mut ids);
+        self.blocks
+            .iter()
+            .chain(self.attachments())
+            .for_each(|value| collect_mentions(value, &mut ids));
+        ids
+    }
+
+    /// Convert the content into Markdown. Without any layout, the text is converted as is.
+    /// Otherwise, the blocks, or the text if there is no block, are converted followed by the
+    /// attachments. The link previews are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// - `resolve`: A function to resolve the name of a user, a user group, or a channel from its
+    ///   ID, used for the mentions.
+    pub fn to_markdown(&self, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+        if !self.has_layout() {
+            return mrkdwn::to_markdown(&self.text, resolve);
+        }
+
+        let body = if self.blocks.is_empty() {
+            mrkdwn::to_markdown(&self.text, resolve)
+        } else {
+            blocks(&self.blocks, resolve)
+        };
+        paragraphs(
+            [body]
+                .into_iter()
+                .chain(self.attachments().map(|a| attachment(a, resolve))),
+        )
+    }
+
+    // The attachments except the link previews.
+    fn attachments(&self) -> impl Iterator<Item = &Value> {
+        self.attachments.iter().filter(|attachment| {
+            attachment.get("from_url").is_none() && attachment["is_app_unfurl"] != true
+        })
+    }
+}
+
+// The type of the given block or element, or empty if unknown.
+fn kind(value: &Value) -> &str {
+    value["type"].as_str().unwrap_or_default()
+}
+
+// The string at the given key of the given object, or empty if missing.
+fn str_of<'a>(value: &'a Value, key: &str) -> &'a str {
+    value[key].as_str().unwrap_or_default()
+}
+
+// The array at the given key of the given object, or empty if missing.
+fn array_of<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value[key].as_array().map_or(&[], Vec::as_slice)
+}
+
+// Join the given paragraphs with a blank line, skipping the empty ones.
+fn paragraphs(paragraphs: impl IntoIterator<Item = String>) -> String {
+    paragraphs
+        .into_iter()
+        .map(|paragraph| paragraph.trim().to_string())
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// Collect the IDs of the users, the user groups, and the channels mentioned in the given value,
+// either as the rich text elements or in the mrkdwn texts without the label.
+fn collect_mentions(value: &Value, ids: &mut BTreeSet<String>) {
+    match value {
+        Value::String(text) => {
+            ids.extend(MENTION.captures_iter(text).map(|caps| caps[1].to_string()));
+        }
+        Value::Array(values) => values.iter().for_each(|value| collect_mentions(value, ids)),
+        Value::Object(object) => {
+            for key in ["user_id", "usergroup_id", "channel_id"] {
+                if let Some(id) = object.get(key).and_then(Value::as_str) {
+                    ids.insert(id.to_string());
+                }
+            }
+            object.values().for_each(|value| collect_mentions(value, ids));
+        }
+        _ => {}
+    }
+}
+
+// Convert the given blocks into Markdown.
+fn blocks(blocks: &[Value], resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    paragraphs(blocks.iter().map(|block| self::block(block, resolve)))
+}
+
+// Convert the given block into Markdown, or a placeholder if it is not supported.
+fn block(block: &Value, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    match kind(block) {
+        "rich_text" => paragraphs(
+            array_of(block, "elements")
+                .iter()
+                .map(|element| rich_text(element, resolve)),
+        ),
+        "header" => format!("### {}", text(&block["text"], resolve)),
+        "section" => {
+            let accessory = &block["accessory"];
+            paragraphs([
+                text(&block["text"], resolve),
+                fields(array_of(block, "fields"), resolve),
+                if kind(accessory) == "image" { image(accessory) } else { String::new() },
+            ])
+        }
+        "context" => array_of(block, "elements")
+            .iter()
+            .filter(|element| kind(element) != "image")
+            .map(|element| text(element, resolve))
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" · "),
+        "divider" => "---".to_string(),
+        "image" => {
+            let title = text(&block["title"], resolve);
+            paragraphs([
+                if title.is_empty() { title } else { format!("**{title}**") },
+                image(block),
+            ])
+        }
+        kind => placeholder(kind),
+    }
+}
+
+// A placeholder for the block or the element of the given type, which is not supported.
+fn placeholder(kind: &str) -> String {
+    format!("*(unsupported {})*", if kind.is_empty() { "block" } else { kind })
+}
+
+// Convert the given text object, either `mrkdwn` or `plain_text`, into Markdown.
+fn text(text: &Value, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    match kind(text) {
+        "mrkdwn" => mrkdwn::to_markdown(str_of(text, "text"), resolve),
+        _ => str_of(text, "text").to_string(),
+    }
+}
+
+// Convert the given fields of a section block into a list, one field per item.
+fn fields(fields: &[Value], resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    fields
+        .iter()
+        .map(|field| text(field, resolve))
+        .filter(|field| !field.trim().is_empty())
+        .map(|field| format!("- {}", field.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Convert the given image block or element into Markdown, or empty if it has no URL.
+fn image(image: &Value) -> String {
+    let url = image["image_url"]
+        .as_str()
+        .or_else(|| image["slack_file"]["url"].as_str())
+        .unwrap_or_default();
+    if url.is_empty() {
+        return String::new();
+    }
+    format!("![{}]({url})", str_of(image, "alt_text"))
+}
+
+// Convert the given element of a rich text block into Markdown.
+fn rich_text(element: &Value, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    let elements = array_of(element, "elements");
+
+    match kind(element) {
+        "rich_text_section" => inline(elements, resolve),
+        "rich_text_list" => {
+            // Limit the depth, as the indent is given by the data.
+            let indent =
+                "  ".repeat(element["indent"].as_u64().unwrap_or_default().min(8) as usize);
+            let ordered = str_of(element, "style") == "ordered";
+            let offset = element["offset"].as_u64().unwrap_or_default();
+            elements
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    // The offset is given by the data, so never overflow.
+                    let number = offset.saturating_add(index as u64 + 1);
+                    let marker = if ordered { format!("{number}.") } else { "-".to_string() };
+                    format!("{indent}{marker} {}", inline(array_of(item, "elements"), resolve))
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        "rich_text_quote" => inline(elements, resolve)
+            .lines()
+            .map(|line| format!("> {line}"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "rich_text_preformatted" => {
+            let code = elements
+                .iter()
+                .map(|element| str_of(element, "text").to_string())
+                .collect::<String>();
+            format!("```\n{}\n```", code.trim_matches('\n'))
+        }
+        kind => placeholder(kind),
+    }
+}
+
+// Convert the given inline elements of a rich text block into Markdown.
+fn inline(elements: &[Value], resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    let name = |id: &str| resolve(id).unwrap_or_else(|| id.to_string());
+
+    elements
+        .iter()
+        .map(|element| match kind(element) {
+            "text" => styled(str_of(element, "text"), &element["style"]),
+            "link" => {
+                let url = str_of(element, "url");
+                match str_of(element, "text") {
+                    "" => format!("<{url}>"),
+                    text => styled(&format!("[{text}]({url})"), &element["style"]),
+                }
+            }
+            "user" => format!("@{}", name(str_of(element, "user_id"))),
+            "usergroup" => format!("@{}", name(str_of(element, "usergroup_id"))),
+            "channel" => format!("#{}", name(str_of(element, "channel_id"))),
+            "emoji" => emoji(str_of(element, "name")),
+            "broadcast" => format!("@{}", str_of(element, "range")),
+            "date" => str_of(element, "fallback").to_string(),
+            "color" => str_of(element, "value").to_string(),
+            _ => str_of(element, "text").to_string(),
+        })
+        .collect()
+}
+
+// Apply the given style of a rich text element to the given text. The surrounding whitespaces
+// are kept out of the markers, which Markdown does not allow inside.
+fn styled(text: &str, style: &Value) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() || !style.is_object() {
+        return text.to_string();
+    }
+
+    let mut styled = trimmed.to_string();
+    for (key, marker) in [("code", "`"), ("strike", "~~"), ("italic", "*"), ("bold", "**")] {
+        if style[key] == true {
+            styled = format!("{marker}{styled}{marker}");
+        }
+    }
+
+    let start = text.len() - text.trim_start().len();
+    let end = text.trim_end().len();
+    format!("{}{styled}{}", &text[..start], &text[end..])
+}
+
+// Convert the given legacy attachment into a Markdown quote, with the color bar as a colored
+// square, preceded by the pretext if any.
+fn attachment(attachment: &Value, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    let link = |text: &str, url: &str| match (text, url) {
+        ("", _) => String::new(),
+        (text, "") => text.to_string(),
+        (text, url) => format!("[{text}]({url})"),
+    };
+    let fields = array_of(attachment, "fields")
+        .iter()
+        .map(|field| {
+            let value = mrkdwn::to_markdown(str_of(field, "value"), resolve);
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            match str_of(field, "title") {
+                "" => format!("- {value}"),
+                title => format!("- **{title}**: {value}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let title = link(str_of(attachment, "title"), str_of(attachment, "title_link"));
+
+    let mut body = paragraphs([
+        link(str_of(attachment, "author_name"), str_of(attachment, "author_link")),
+        if title.is_empty() { title } else { format!("**{title}**") },
+        mrkdwn::to_markdown(str_of(attachment, "text"), resolve),
+        fields,
+        blocks(array_of(attachment, "blocks"), resolve),
+        image(attachment),
+        str_of(attachment, "footer").to_string(),
+    ]);
+    if body.is_empty() {
+        body = str_of(attachment, "fallback").trim().to_string();
+    }
+    if body.is_empty() {
+        return mrkdwn::to_markdown(str_of(attachment, "pretext"), resolve);
+    }
+    if let Some(square) = color(str_of(attachment, "color")) {
+        body = format!("{square} {body}");
+    }
+
+    let quote = body
+        .lines()
+        .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {line}") })
+        .collect::<Vec<_>>()
+        .join("\n");
+    paragraphs([mrkdwn::to_markdown(str_of(attachment, "pretext"), resolve), quote])
+}
+
+// The colored square closest to the given color of an attachment, either `good`, `warning`,
+// `danger`, or a hex color code such as `#36a64f`.
+fn color(color: &str) -> Option<&'static str> {
+    let hex = match color {
+        "good" => "2eb67d",
+        "warning" => "ecb22e",
+        "danger" => "e01e5a",
+        color => color.trim_start_matches('#'),
+    };
+    let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+    let [r, g, b] = [16, 8, 0].map(|shift| ((rgb >> shift) & 0xff) as f64 / 255.0);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    if max - min < 0.15 {
+        return Some(if max < 0.5 { "⬛" } else { "⬜" });
+    }
+
+    let hue = if max == r {
+        60.0 * ((g - b) / (max - min)).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / (max - min) + 2.0)
+    } else {
+        60.0 * ((r - g) / (max - min) + 4.0)
+    };
+    Some(match hue {
+        hue if hue < 15.0 => "🟥",
+        hue if hue < 40.0 => "🟧",
+        hue if hue < 70.0 => "🟨",
+        hue if hue < 170.0 => "🟩",
+        hue if hue < 260.0 => "🟦",
+        hue if hue < 330.0 => "🟪",
+        _ => "🟥",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde_json::json;
+
+    use crate::slack::blocks::Content;
+
+    fn content(value: serde_json::Value) -> Content {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let resolve = |id: &str| (id == "U0123").then(|| "jake".to_string());
+
+        let alert = content(json!({
+            "text": "Incident triggered",
+            "blocks": [
+                { "type": "header", "text": { "type": "plain_text", "text": "Incident #42" } },
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": "*High* error rate, cc <@U0123>" },
+                    "fields": [
+                        { "type": "mrkdwn", "text": "*Service:*\napi" },
+                        { "type": "mrkdwn", "text": "*Urgency:*\nhigh" }
+                    ]
+                },
+                { "type": "divider" },
+                {
+                    "type": "context",
+                    "elements": [
+                        { "type": "image", "image_url": "https://example.com/icon.png" },
+                        { "type": "mrkdwn", "text": "via PagerDuty" }
+                    ]
+                },
+                {
+                    "type": "image",
+                    "image_url": "https://example.com/graph.png",
+                    "alt_text": "graph"
+                },
+                { "type": "actions", "elements": [] }
+            ]
+        }));
+        assert!(alert.has_layout());
+        assert_eq!(
+            alert.to_markdown(&resolve),
+            "### Incident #42\n\n**High** error rate, cc @jake\n\n- **Service:** api\n- \
+             **Urgency:** high\n\n---\n\nvia PagerDuty\n\n![graph](https://example.com/graph.png)\
+             \n\n*(unsupported actions)*"
+        );
+
+        let ci = content(json!({
+            "text": "",
+            "attachments": [
+                {
+                    "color": "danger",
+                    "pretext": "Build failed",
+                    "title": "main #128",
+                    "title_link": "https://ci.example.com/128",
+                    "fields": [{ "title": "Branch", "value": "main", "short": true }],
+                    "footer": "CI"
+                },
+                { "from_url": "https://example.com", "title": "Link preview" }
+            ]
+        }));
+        assert_eq!(
+            ci.to_markdown(&resolve),
+            "Build failed\n\n> 🟥 **[main #128](https://ci.example.com/128)**\n>\n> - **Branch**: \
+             main\n>\n> CI"
+        );
+
+        let rich_text = content(json!({
+            "text": "fallback",
+            "blocks": [
+                {
+                    "type": "rich_text",
+                    "elements": [
+                        {
+                            "type": "rich_text_list",
+                            "style": "ordered",
+                            "elements": [{
+                                "type": "rich_text_section",
+                                "elements": [
+                                    { "type": "text", "text": "bold ", "style": { "bold": true } },
+                                    { "type": "emoji", "name": "tada" }
+                                ]
+                            }]
+                        }
+                    ]
+                },
+                { "type": "section", "text": { "type": "plain_text", "text": "by bot" } }
+            ]
+        }));
+        assert_eq!(rich_text.to_markdown(&resolve), "1. **bold** 🎉\n\nby bot");
+
+        let mentions = content(json!({
+            "text": "<!subteam^S0456> <#C0123>",
+            "blocks": [{
+                "type": "rich_text",
+                "elements": [{
+                    "type": "rich_text_section",
+                    "elements": [
+                        { "type": "user", "user_id": "U0123" },
+                        { "type": "usergroup", "usergroup_id": "S0123" }
+                    ]
+                }]
+            }]
+        }));
+        assert_eq!(
+            mentions.mentions(),
+            BTreeSet::from(["C0123", "S0123", "S0456", "U0123"].map(String::from))
+        );
+
+        // Only the rich text, which is left to the text.
+        let plain = content(json!({ "text": "*hi*", "blocks": [{ "type": "rich_text" }] }));
+        assert!(!plain.has_layout());
+        assert_eq!(plain.to_markdown(&resolve), "**hi**");
+
+        // Malformed blocks never panic.
+        let malformed = content(json!({
+            "blocks": [
+                1,
+                "section",
+                { "type": 3 },
+                { "type": "section", "text": "nope", "fields": { "a": 1 } },
+                { "type": "rich_text", "elements": [{ "type": "rich_text_list", "indent": 1e10 }] },
+                {
+                    "type": "rich_text",
+                    "elements": [{
+                        "type": "rich_text_list",
+                        "style": "ordered",
+                        "offset": u64::MAX,
+                        "elements": [{}, {}]
+                    }]
+                }
+            ],
+            "attachments": [{ "color": "#zzzzzz" }, { "color": "ü" }, []]
+        }));
+        malformed.to_markdown(&resolve);
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fs::{read_dir, read_to_string, File},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Result};
+use jiff::{tz::TimeZone, Timestamp};
+use serde::{de::DeserializeOwned, Deserialize};
+use url::Url;
+use zip::{result::ZipError, ZipArchive};
+
+use crate::{
+    client::{self, Message, Metadata},
+    slack::{self, parse_ts, Content, Edited, Permalink, Reaction, Text},
+};
+
+/// A [Slack workspace export](https://slack.com/help/articles/201658943), either extracted to a
+/// directory or as the ZIP archive, used to resolve messages without the Web API.
+pub struct Export {
+    archive: Archive,
+
+    /// The names of the users, keyed by the user ID.
+    users: HashMap<String, String>,
+
+    /// The profiles of the users, keyed by the user ID.
+    profiles: HashMap<String, Profile>,
+
+    /// The channels, keyed by the channel ID.
+    channels: HashMap<String, Channel>,
+}
+
+enum Archive {
+    Directory(PathBuf),
+    /// The archive is opened once and shared, since reading its central directory is not cheap.
+    Zip(Mutex<ZipArchive<File>>),
+}
+
+struct Channel {
+    name: String,
+    is_private: bool,
+    topic: String,
+    purpose: String,
+    /// The directory which contains the per-day JSON files of the channel.
+    directory: String,
+}
+
+#[derive(Default)]
+struct Profile {
+    real_name: String,
+    display_name: String,
+    avatar: String,
+    title: String,
+    tz: String,
+}
+
+#[derive(Deserialize)]
+struct ExportUser {
+    id: String,
+    name: String,
+    real_name: Option<String>,
+    #[serde(default)]
+    profile: ExportProfile,
+    tz: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ExportProfile {
+    display_name: Option<String>,
+    title: Option<String>,
+    image_192: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExportChannel {
+    id: String,
+    name: Option<String>,
+    #[serde(default)]
+    members: Vec<String>,
+    #[serde(default)]
+    topic: Text,
+    #[serde(default)]
+    purpose: Text,
+}
+
+#[derive(Deserialize)]
+struct ExportMessage {
+    ts: String,
+    user: Option<String>,
+    username: Option<String>,
+    #[serde(flatten)]
+    content: Content,
+    thread_ts: Option<String>,
+    edited: Option<Edited>,
+    reply_count: Option<u64>,
+    #[serde(default)]
+    reactions: Vec<Reaction>,
+    #[serde(default)]
+    files: Vec<slack::File>,
+}
+
+impl Export {
+    /// Open the export at the given path, which is either a directory or a ZIP archive, and load
+    /// the users and the channels.
+    pub fn open(path: &Path) -> Result<Self> {
+        let archive = if path.is_dir() {
+            Archive::Directory(path.to_path_buf())
+        } else if path.is_file() {
+            let archive = File::open(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| Ok(ZipArchive::new(file)?))
+                .map_err(|why| anyhow!("failed to open {}: {why}", path.display()))?;
+            Archive::Zip(Mutex::new(archive))
+        } else {
+            return Err(anyhow!("{} does not exist", path.display()));
+        };
+
+        let mut users = HashMap::new();
+        let mut profiles = HashMap::new();
+        for user in archive
+            .read_json::<Vec<ExportUser>>("users.json")?
+            .ok_or_else(|| anyhow!("users.json is not found in {}", path.display()))?
+        {
+            let name = user
+                .profile
+                .display_name
+                .clone()
+                .filter(|name| !name.is_empty())
+                .or(user.real_name.clone())
+                .filter(|name| !name.is_empty())
+                .unwrap_or(user.name);
+            let profile = Profile {
+                real_name: user.real_name.unwrap_or_default(),
+                display_name: user.profile.display_name.unwrap_or_default(),
+                avatar: user.profile.image_192.unwrap_or_default(),
+                title: user.profile.title.unwrap_or_default(),
+                tz: user.tz.unwrap_or_default(),
+            };
+            users.insert(user.id.clone(), name);
+            profiles.insert(user.id, profile);
+        }
+
+        let mut channels = HashMap::new();
+        for (file, is_private) in [
+            ("channels.json", false),
+            ("groups.json", true),
+            ("mpims.json", true),
+            ("dms.json", true),
+        ] {
+            for channel in archive.read_json::<Vec<ExportChannel>>(file)?.unwrap_or_default() {
+                // Direct messages have no name, and their directories are named after the ID.
+                let (name, directory) = match channel.name {
+                    Some(name) => (name.clone(), name),
+                    None => (
+                        channel
+                            .members
+                            .iter()
+                            .map(|member| users.get(member).unwrap_or(member).as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        channel.id.clone(),
+                    ),
+                };
+                channels.insert(
+                    channel.id,
+                    Channel {
+                        name,
+                        is_private,
+                        topic: channel.topic.value,
+                        purpose: channel.purpose.value,
+                        directory,
+                    },
+                );
+            }
+        }
+
+        Ok(Self { archive, users, profiles, channels })
+    }
+
+    /// Resolve the message the given URL points to.
+    pub fn resolve(&self, url: &Url) -> Result<Message> {
+        let permalink = Permalink::try_from(url)?;
+        let channel = self.channel(&permalink.channel)?;
+        let ts = parse_ts(&permalink.ts)?;
+
+        // The per-day files might be split in a time zone other than UTC, so look around.
+        let date = Timestamp::from_microsecond(ts)?.to_zoned(TimeZone::UTC).date();
+        for date in [date, date.yesterday()?, date.tomorrow()?] {
+            let path = format!("{}/{date}.json", channel.directory);
+            let Some(messages) = self.archive.read_json::<Vec<ExportMessage>>(&path)? else {
+                continue;
+            };
+
+            if let Some(message) = messages.into_iter().find(|message| message.ts == permalink.ts) {
+                let empty = Profile::default();
+                let profile = message
+                    .user
+                    .as_ref()
+                    .and_then(|user| self.profiles.get(user))
+                    .unwrap_or(&empty);
+                // The export has no information about the workspace but the URL.
+                let metadata = Metadata {
+                    user_real_name: profile.real_name.clone(),
+                    user_display_name: profile.display_name.clone(),
+                    user_avatar: profile.avatar.clone(),
+                    user_title: profile.title.clone(),
+                    user_tz: profile.tz.clone(),
+                    workspace_name: String::new(),
+                    workspace_domain: permalink
+                        .host
+                        .split('.')
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    channel_topic: channel.topic.clone(),
+                    channel_purpose: channel.purpose.clone(),
+                    is_edited: message.edited.is_some(),
+                    edited_ts: message.edited.map(|edited| edited.ts).unwrap_or_default(),
+                    reply_count: message.reply_count.unwrap_or_default(),
+                    reactions: message.reactions.iter().map(client::Reaction::from).collect(),
+                    files: message.files.iter().map(client::File::from).collect(),
+                    thread_ts: message.thread_ts.unwrap_or_default(),
+                };
+                return Ok(Message {
+                    channel_name: channel.name.clone(),
+                    is_private_channel: channel.is_private,
+                    user_name: message
+                        .user
+                        .and_then(|user| self.users.get(&user).cloned())
+                        .or(message.username)
+                        .unwrap_or_else(|| "unknown".to_string()),
+                    url: url.to_string(),
+                    ts,
+                    metadata,
+                    body: message.content.to_markdown(&|id: &str| self.name(id)),
+                });
+            }
+        }
+
+        Err(anyhow!("message {} is not found in the export", permalink.ts))
+    }
+
+    /// List the timestamps of the parent message and all the replies of the thread, in
+    /// chronological order.
+    pub fn replies(&self, channel: &str, thread_ts: &str) -> Result<Vec<String>> {
+        let channel = self.channel(channel)?;
+        let mut replies = Vec::new();
+
+        for path in self.archive.list(&channel.directory)? {
+            let messages = self
+                .archive
+                .read_json::<Vec<ExportMessage>>(&path)?
+                .unwrap_or_default();
+            for message in messages {
+                if message.ts == thread_ts || message.thread_ts.as_deref() == Some(thread_ts) {
+                    replies.push((parse_ts(&message.ts)?, message.ts));
+                }
+            }
+        }
+        replies.sort();
+
+        Ok(replies.into_iter().map(|(_, ts)| ts).collect())
+    }
+
+    fn channel(&self, id: &str) -> Result<&Channel> {
+        self.channels
+            .get(id)
+            .ok_or_else(|| anyhow!("channel {id} is not found in the export"))
+    }
+
+    // Resolve the name of the user or the channel with the given ID.
+    fn name(&self, id: &str) -> Option<String> {
+        self.users
+            .get(id)
+            .or_else(|| self.channels.get(id).map(|channel| &channel.name))
+            .cloned()
+    }
+}
+
+impl Archive {
+    // Read and deserialize the JSON file at the given path relative to the root of the export,
+    // or `None` if it does not exist.
+    fn read_json<T>(&self, path: &str) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let content = match self {
+            Archive::Directory(root) => {
+                let path = root.join(path);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                read_to_string(path)?
+            }
+            Archive::Zip(archive) => {
+                let mut archive = archive.lock().unwrap();
+                let mut file = match archive.by_name(path) {
+                    Ok(file) => file,
+                    Err(ZipError::FileNotFound) => return Ok(None),
+                    Err(why) => return Err(why.into()),
+                };
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                content
+            }
+        };
+
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|why| anyhow!("failed to parse {path}: {why}"))
+    }
+
+    // List the JSON files in the given directory relative to the root of the export.
+    fn list(&self, directory: &str) -> Result<Vec<String>> {
+        let prefix = format!("{directory}/");
+        let mut paths = match self {
+            Archive::Directory(root) => read_dir(root.join(directory))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| format!("{prefix}{}", entry.file_name().to_string_lossy()))
+                .collect::<Vec<_>>(),
+            Archive::Zip(archive) => archive
+                .lock()
+                .unwrap()
+                .file_names()
+                .filter(|name| name.starts_with(&prefix))
+                .map(String::from)
+                .collect(),
+        };
+        paths.retain(|path| path.ends_with(".json"));
+
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{read, File},
+        io::Write,
+        path::{Path, PathBuf},
+    };
+
+    use anyhow::Result;
+    use url::Url;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::slack::Export;
+
+    const FILES: [&str; 4] =
+        ["users.json", "channels.json", "general/2024-08-27.json", "general/2024-08-28.json"];
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/export")
+    }
+
+    #[test]
+    fn test_export() -> Result<()> {
+        let zip = temp_dir().join(format!("sam-export-{}.zip", std::process::id()));
+        let mut writer = ZipWriter::new(File::create(&zip)?);
+        for file in FILES {
+            writer.start_file(file, SimpleFileOptions::default())?;
+            writer.write_all(&read(fixture().join(file))?)?;
+        }
+        writer.finish()?;
+
+        for export in [Export::open(&fixture())?, Export::open(&zip)?] {
+            let url = Url::parse("https://xxx.slack.com/archives/C0123/p1724743600000100")?;
+            let message = export.resolve(&url)?;
+            assert_eq!(message.channel_name, "general");
+            assert!(!message.is_private_channel);
+            // The empty display name falls back to the real name.
+            assert_eq!(message.user_name, "Jake Doe");
+            assert_eq!(message.url, url.as_str());
+            assert_eq!(message.ts, 1724743600000100);
+            assert_eq!(message.body, "Shall we ship it, @finn?");
+
+            let url = Url::parse("https://xxx.slack.com/archives/C0123/p1724743664325609")?;
+            let message = export.resolve(&url)?;
+            assert_eq!(message.user_name, "finn");
+            assert_eq!(message.body, "**Yes**, see #general");
+
+            let url = Url::parse("https://xxx.slack.com/archives/C0123/p1724743700000000")?;
+            assert_eq!(export.resolve(&url)?.user_name, "deploy-bot");
+
+            let url = Url::parse("https://xxx.slack.com/archives/C0123/p1724743601000000")?;
+            assert!(export.resolve(&url).is_err());
+            let url = Url::parse("https://xxx.slack.com/archives/C9999/p1724743600000100")?;
+            assert!(export.resolve(&url).is_err());
+
+            assert_eq!(
+                export.replies("C0123", "1724743600.000100")?,
+                ["1724743600.000100", "1724743664.325609", "1724803200.000000"]
+            );
+        }
+
+        assert!(Export::open(&fixture().join("missing")).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+mod blocks;
+mod export;
+mod mrkdwn;
+mod permalink;
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+pub use blocks::Content;
+pub use export::Export;
+pub use permalink::Permalink;
+pub use reqwest::Client as HttpClient;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+const BASE_URL: &str = "https://slack.com/api";
+
+/// A minimal Slack Web API client, for the data which [`slack_client`] does not provide.
+pub struct Api<'token> {
+    client: HttpClient,
+    token: &'token str,
+}
+
+/// A Slack message, as returned by the Web API. Only the fields used by this crate are listed.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Message {
+    /// The timestamp of the message, which is also the ID of the message.
+    pub ts: String,
+
+    /// The ID of the user who posted the message, or `None` for a bot or an integration.
+    pub user: Option<String>,
+
+    /// The name of the bot or the integration which posted the message, if any.
+    pub username: Option<String>,
+
+    /// The text, the blocks, and the attachments of the message.
+    #[serde(flatten)]
+    pub content: Content,
+
+    /// The last edit of the message, if edited.
+    pub edited: Option<Edited>,
+
+    /// The number of the replies, if the message is the parent of a thread.
+    pub reply_count: Option<u64>,
+
+    /// The timestamp of the parent message, if the message is in a thread.
+    pub thread_ts: Option<String>,
+
+    /// The reactions to the message, in the order they were first added.
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+
+    /// The files shared with the message.
+    #[serde(default)]
+    pub files: Vec<File>,
+}
+
+/// The last edit of a Slack message.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Edited {
+    /// The timestamp of the edit.
+    pub ts: String,
+}
+
+/// A reaction to a Slack message.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Reaction {
+    /// The name of the emoji without the colons, e.g. `white_check_mark` or `+1::skin-tone-2`.
+    pub name: String,
+
+    /// The number of the users who reacted with the emoji.
+    pub count: u64,
+}
+
+/// A file shared with a Slack message. Only the fields used by this crate are listed, which are
+/// empty for the files not accessible any more, e.g. deleted ones.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct File {
+    /// The ID of the file.
+    pub id: String,
+
+    /// The name of the file, e.g. `screenshot.png`.
+    pub name: String,
+
+    /// The MIME type of the file, e.g. `image/png`.
+    pub mimetype: String,
+
+    /// The size of the file, in bytes.
+    pub size: u64,
+
+    /// The Slack URL of the file, which shows it in Slack.
+    pub permalink: String,
+
+    /// The URL to download the file, which requires the token.
+    pub url_private_download: String,
+
+    /// The URL of the 480px thumbnail, for images.
+    pub thumb_480: String,
+}
+
+/// A Slack user, as returned by the Web API. Only the fields used by this crate are listed.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct User {
+    /// The real name of the user.
+    pub real_name: Option<String>,
+
+    /// The IANA time zone database identifier the user set in the profile.
+    pub tz: Option<String>,
+
+    /// The profile of the user.
+    #[serde(default)]
+    pub profile: Profile,
+}
+
+/// The profile of a Slack user.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Profile {
+    /// The display name of the user, which might be empty.
+    pub display_name: Option<String>,
+
+    /// The title of the user, e.g. the job title.
+    pub title: Option<String>,
+
+    /// The URL of the 192x192 avatar image of the user.
+    pub image_192: Option<String>,
+}
+
+/// A Slack channel, as returned by the Web API. Only the fields used by this crate are listed.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Channel {
+    /// The name of the channel.
+    pub name: String,
+
+    /// Whether the channel is a private channel or not.
+    pub is_private: bool,
+
+    /// The topic of the channel.
+    pub topic: Text,
+
+    /// The purpose of the channel.
+    pub purpose: Text,
+}
+
+/// A text with its metadata, e.g. the topic of a channel.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Text {
+    /// The text itself.
+    pub value: String,
+}
+
+/// A Slack workspace, as returned by the Web API. Only the fields used by this crate are listed.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Team {
+    /// The name of the workspace.
+    pub name: String,
+
+    /// The domain of the workspace, i.e. `<domain>.slack.com`.
+    pub domain: String,
+}
+
+/// A Slack user group, as returned by the Web API. Only the fields used by this crate are listed.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Usergroup {
+    /// The ID of the user group.
+    pub id: String,
+
+    /// The handle of the user group, which is used to mention it, e.g. `eng` for `@eng`.
+    pub handle: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    user: User,
+}
+
+#[derive(Deserialize)]
+struct ChannelInfo {
+    channel: Channel,
+}
+
+#[derive(Deserialize)]
+struct TeamInfo {
+    team: Team,
+}
+
+#[derive(Deserialize)]
+struct Usergroups {
+    usergroups: Vec<Usergroup>,
+}
+
+#[derive(Deserialize)]
+struct Replies {
+    messages: Vec<Message>,
+    response_metadata: Option<ResponseMetadata>,
+}
+
+#[derive(Deserialize)]
+struct ResponseMetadata {
+    next_cursor: Option<String>,
+}
+
+/// Parse the given Slack timestamp, e.g. `1724743664.325609`, into microseconds.
+pub fn parse_ts(ts: &str) -> Result<i64> {
+    ts.replace('.', "")
+        .parse()
+        .map_err(|why| anyhow!("{ts} is not a valid timestamp: {why}"))
+}
+
+/// The emoji of the given name without the colons, e.g. `✅` for `white_check_mark`, or the name
+/// in colons for the custom ones, e.g. `:party-parrot:`. The skin tone appended to the name, e.g.
+/// `+1::skin-tone-2`, is ignored.
+pub fn emoji(name: &str) -> String {
+    let name = name.split("::").next().unwrap_or(name);
+    emojis::get_by_shortcode(name).map_or_else(|| format!(":{name}:"), |emoji| emoji.to_string())
+}
+
+/// Format the given microseconds into a Slack timestamp, e.g. `1724743664.325609`.
+pub fn format_ts(micros: i64) -> String {
+    format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+}
+
+impl<'token> Api<'token> {
+    /// Create a new Web API client with the given HTTP client, which is cheap to clone and shares
+    /// the connections, and the Slack API token.
+    pub fn new(client: &HttpClient, token: &'token str) -> Self {
+        Self { client: client.clone(), token }
+    }
+
+    /// Retrieve the parent message and all the replies of the thread, in chronological order.
+    ///
+    /// # Arguments
+    ///
+    /// - `channel`: The ID of the channel the thread belongs to.
+    /// - `thread_ts`: The timestamp of the parent message.
+    pub async fn replies(&self, channel: &str, thread_ts: &str) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut query = vec![("channel", channel), ("ts", thread_ts), ("limit", "200")];
+            if let Some(ref cursor) = cursor {
+                query.push(("cursor", cursor.as_str()));
+            }
+
+            let replies: Replies = self.call("conversations.replies", &query).await?;
+            messages.extend(replies.messages);
+
+            cursor = replies
+                .response_metadata
+                .and_then(|metadata| metadata.next_cursor)
+                .filter(|cursor| !cursor.is_empty());
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Retrieve the message with the given timestamp.
+    ///
+    /// # Arguments
+    ///
+    /// - `channel`: The ID of the channel the message belongs to.
+    /// - `thread_ts`: The timestamp of the parent message, if the message is a reply.
+    /// - `ts`: The timestamp of the message.
+    pub async fn message(
+        &self,
+        channel: &str,
+        thread_ts: Option<&str>,
+        ts: &str,
+    ) -> Result<Message> {
+        // The parent message is always returned first, so look for the one with the timestamp.
+        let query = [
+            ("channel", channel),
+            ("ts", thread_ts.unwrap_or(ts)),
+            ("oldest", ts),
+            ("latest", ts),
+            ("inclusive", "true"),
+        ];
+        let replies: Replies = self.call("conversations.replies", &query).await?;
+
+        replies
+            .messages
+            .into_iter()
+            .find(|message| message.ts == ts)
+            .ok_or_else(|| anyhow!("message {ts} is not found in {channel}"))
+    }
+
+    /// Retrieve the user with the given ID.
+    pub async fn user(&self, id: &str) -> Result<User> {
+        let info: UserInfo = self.call("users.info", &[("user", id)]).await?;
+        Ok(info.user)
+    }
+
+    /// Retrieve the channel with the given ID.
+    pub async fn channel(&self, id: &str) -> Result<Channel> {
+        let info: ChannelInfo = self.call("conversations.info", &[("channel", id)]).await?;
+        Ok(info.channel)
+    }
+
+    /// Retrieve all the user groups of the workspace, as there is no method to retrieve one.
+    pub async fn usergroups(&self) -> Result<Vec<Usergroup>> {
+        let usergroups: Usergroups = self.call("usergroups.list", &[]).await?;
+        Ok(usergroups.usergroups)
+    }
+
+    /// Retrieve the workspace the token belongs to.
+    pub async fn team(&self) -> Result<Team> {
+        let info: TeamInfo = self.call("team.info", &[]).await?;
+        Ok(info.team)
+    }
+
+    // Call the given Web API method with the query, and deserialize the response. The token is
+    // sent as a header, so it never appears in the URL nor in the error messages.
+    async fn call<T>(&self, method: &str, query: &[(&str, &str)]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self
+            .client
+            .get(format!("{BASE_URL}/{method}"))
+            .bearer_auth(self.token)
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        if response["ok"].as_bool() != Some(true) {
+            return Err(anyhow!(
+                "{method} failed: {}",
+                response["error"].as_str().unwrap_or("unknown error")
+            ));
+        }
+
+        Ok(serde_json::from_value(response)?)
+    }
+}
+
+/// Download the file at the given URL, e.g. [`File::url_private_download`], to the given path
+/// with the given HTTP client, and the token if any. The files in an export might be downloaded
+/// without the token, as their URLs have the access token in the query.
+pub async fn download(
+    client: &HttpClient,
+    url: &str,
+    token: Option<&str>,
+    path: &Path,
+) -> Result<()> {
+    // Drop the URL from the errors, as it might contain the access token.
+    let failed = |why: reqwest::Error| {
+        anyhow!("failed to download {}: {}", path.display(), why.without_url())
+    };
+
+    let mut request = client.get(url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let content = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(failed)?
+        .bytes()
+        .await
+        .map_err(failed)?;
+
+    tokio::fs::write(path, content)
+        .await
+        .map_err(|why| anyhow!("failed to write {}: {why}", path.display()))
+}
@@ -0,0 +1,114 @@
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+/// A special entity such as a link or a mention, e.g. `<https://example.com|label>` or `<@U0123>`.
+static ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([^<>|]+)(?:\|([^<>]*))?>").unwrap());
+static BOLD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(^|[\s(])\*([^*\n]+)\*").unwrap());
+static ITALIC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(^|[\s(])_([^_\n]+)_").unwrap());
+static STRIKE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(^|[\s(])~([^~\n]+)~").unwrap());
+
+/// Convert the given Slack [mrkdwn](https://api.slack.com/reference/surfaces/formatting) text
+/// into Markdown.
+///
+/// # Arguments
+///
+/// - `text`: The mrkdwn text.
+/// - `resolve`: A function to resolve the name of a user, a user group, or a channel from its ID,
+///   used for the mentions without a label.
+pub fn to_markdown(text: &str, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    text.split("```")
+        .enumerate()
+        .map(|(i, block)| {
+            if i % 2 == 1 {
+                format!("\n```\n{}\n```\n", unescape(block.trim_matches('\n')))
+            } else {
+                block
+                    .split('`')
+                    .enumerate()
+                    .map(|(j, span)| {
+                        if j % 2 == 1 {
+                            format!("`{}`", unescape(span))
+                        } else {
+                            inline(span, resolve)
+                        }
+                    })
+                    .collect()
+            }
+        })
+        .collect()
+}
+
+// Convert the entities and the text styles of the given text, which contains no code.
+fn inline(text: &str, resolve: &dyn Fn(&str) -> Option<String>) -> String {
+    let text = ENTITY.replace_all(text, |caps: &Captures| {
+        let target = &caps[1];
+        let label = caps
+            .get(2)
+            .map(|label| label.as_str())
+            .filter(|label| !label.is_empty());
+        let name = |id: &str| {
+            label
+                .map(String::from)
+                .or_else(|| resolve(id))
+                .unwrap_or_else(|| id.to_string())
+        };
+
+        if let Some(id) = target.strip_prefix('@') {
+            format!("@{}", name(id))
+        } else if let Some(id) = target.strip_prefix('#') {
+            format!("#{}", name(id))
+        } else if let Some(special) = target.strip_prefix('!') {
+            match (label, special.strip_prefix("subteam^")) {
+                (Some(label), _) => label.to_string(),
+                (None, Some(id)) => format!("@{}", name(id)),
+                (None, None) => format!("@{}", special.split('^').next().unwrap_or(special)),
+            }
+        } else {
+            match label {
+                Some(label) => format!("[{label}]({target})"),
+                None => format!("<{target}>"),
+            }
+        }
+    });
+
+    let text = BOLD.replace_all(&text, "${1}**${2}**");
+    let text = ITALIC.replace_all(&text, "${1}*${2}*");
+    let text = STRIKE.replace_all(&text, "${1}~~${2}~~");
+
+    unescape(&text)
+}
+
+// Decode the HTML entities Slack escapes in the text.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::slack::mrkdwn::to_markdown;
+
+    #[test]
+    fn test_to_markdown() {
+        let resolve = |id: &str| match id {
+            "U0123" => Some("jake".to_string()),
+            "S0123" => Some("eng".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            to_markdown("*bold* _italic_ ~strike~ `*code*` &lt;3", &resolve),
+            "**bold** *italic* ~~strike~~ `*code*` <3"
+        );
+        assert_eq!(
+            to_markdown(
+                "<@U0123> <#C0123|general> <!here> <!subteam^S0123> <!subteam^S0456|@ops> \
+                 <https://example.com|example>",
+                &resolve
+            ),
+            "@jake #general @here @eng @ops [example](https://example.com)"
+        );
+        assert_eq!(to_markdown("see ```a &amp;&amp; b```", &resolve), "see \n```\na && b\n```\n");
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use url::Url;
+
+/// The components of a Slack message URL, i.e. a permalink, such as
+/// `https://xxx.slack.com/archives/C01234567/p1724743664325609?thread_ts=1724743600.000100`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Permalink {
+    /// The host of the workspace, e.g. `xxx.slack.com`.
+    pub host: String,
+
+    /// The ID of the channel the message belongs to.
+    pub channel: String,
+
+    /// The timestamp of the message, which is also the ID of the message, e.g.
+    /// `1724743664.325609`.
+    pub ts: String,
+
+    /// The timestamp of the parent message, if the message is a reply in a thread.
+    pub thread_ts: Option<String>,
+}
+
+impl TryFrom<&Url> for Permalink {
+    type Error = anyhow::Error;
+
+    fn try_from(url: &Url) -> Result<Self> {
+        let host = url.host_str().ok_or_else(|| anyhow!("{url} has no host"))?;
+        let segments = url.path_segments().map(|s| s.collect::<Vec<_>>()).unwrap_or_default();
+
+        let (channel, id) = match segments.as_slice() {
+            ["archives", channel, id, ..] => (*channel, *id),
+            _ => return Err(anyhow!("{url} is not a Slack message URL")),
+        };
+
+        let digits = id
+            .strip_prefix('p')
+            .filter(|digits| digits.len() > 6 && digits.chars().all(|c| c.is_ascii_digit()))
+            .ok_or_else(|| anyhow!("{url} does not contain a valid message ID"))?;
+        let (seconds, micros) = digits.split_at(digits.len() - 6);
+
+        Ok(Self {
+            host: host.to_string(),
+            channel: channel.to_string(),
+            ts: format!("{seconds}.{micros}"),
+            thread_ts: url
+                .query_pairs()
+                .find(|(key, _)| key == "thread_ts")
+                .map(|(_, value)| value.to_string()),
+        })
+    }
+}
+
+impl Permalink {
+    /// Build the URL of another message in the same channel, which is a reply in the thread
+    /// started by `thread_ts` unless `ts` is the parent itself.
+    pub fn url_for(&self, ts: &str, thread_ts: &str) -> Result<Url> {
+        let mut url = Url::parse(&format!(
+            "https://{}/archives/{}/p{}",
+            self.host,
+            self.channel,
+            ts.replace('.', "")
+        ))?;
+
+        if ts != thread_ts {
+            url.query_pairs_mut()
+                .append_pair("thread_ts", thread_ts)
+                .append_pair("cid", &self.channel);
+        }
+
+        Ok(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use url::Url;
+
+    use crate::slack::Permalink;
+
+    #[test]
+    fn test_permalink() -> Result<()> {
+        let url = Url::parse("https://xxx.slack.com/archives/C01234567/p1724743664325609?thread_ts=1724743600.000100&cid=C01234567")?;
+        let permalink = Permalink::try_from(&url)?;
+
+        assert_eq!(permalink.host, "xxx.slack.com");
+        assert_eq!(permalink.channel, "C01234567");
+        assert_eq!(permalink.ts, "1724743664.325609");
+        assert_eq!(permalink.thread_ts.as_deref(), Some("1724743600.000100"));
+        assert_eq!(permalink.url_for("1724743664.325609", "1724743600.000100")?, url);
+        assert_eq!(
+            permalink.url_for("1724743600.000100", "1724743600.000100")?.as_str(),
+            "https://xxx.slack.com/archives/C01234567/p1724743600000100"
+        );
+
+        assert!(Permalink::try_from(&Url::parse("https://xxx.slack.com/client/T0/C0")?).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use std::{collections::BTreeSet, error::Error, fmt, sync::LazyLock};
+
+use regex::Regex;
+use strum::VariantArray;
+use tera::{
+    ast::{Expr, ExprVal, FunctionCall, Node},
+    Template,
+};
+
+use crate::template::{setup_tera, ContextKey, TemplateSource, TemplateType::*};
+
+/// The position the Tera parser reports in an error, e.g. `--> 3:7`.
+static POSITION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"--> (\d+):\d+").unwrap());
+
+/// An identifier which is not an attribute, e.g. `user_name` but not `name` in `file.name`.
+static IDENTIFIER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^\w.])([A-Za-z_]\w*)").unwrap());
+
+/// The severity of a finding.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The template fails to render.
+    Error,
+
+    /// The template renders, but might not as expected.
+    Warning,
+}
+
+/// A problem found in a template.
+#[derive(Debug)]
+pub struct Finding {
+    /// The severity of the problem.
+    pub severity: Severity,
+
+    /// Where the template comes from, i.e. the path to the file, the option, or `built-in`.
+    pub origin: String,
+
+    /// The line number of the problem in the template, if known.
+    pub line: Option<usize>,
+
+    /// The description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "{severity}: {}:{line}: {}", self.origin, self.message),
+            None => write!(f, "{severity}: {}: {}", self.origin, self.message),
+        }
+    }
+}
+
+/// Check the given template sources without rendering them, i.e. compile them all, and look for
+/// the variables which are not [`ContextKey`]s, and the ones which are available only with a
+/// quote in the templates without a quote. The built-in defaults, `builtin/<name>`, are not
+/// checked.
+pub fn check(sources: &[TemplateSource]) -> Vec<Finding> {
+    let checked = sources.iter().filter(|source| !source.name.starts_with("builtin/"));
+    let mut findings = Vec::new();
+
+    // Parse each template on its own first, to tell which one is broken.
+    for source in checked.clone() {
+        if let Err(why) = Template::new(&source.name, None, &source.content) {
+            findings.push(error_finding(source.origin.clone(), &why));
+        }
+    }
+    if !findings.is_empty() {
+        return findings;
+    }
+
+    // Then set up them all, to find the missing parents, includes, or macros.
+    // The time zone and the locale do not matter, as nothing is rendered.
+    let tera = match setup_tera(sources, "UTC", None) {
+        Ok(tera) => tera,
+        Err(why) => return vec![error_finding("templates".to_string(), &*why)],
+    };
+
+    let keys = ContextKey::VARIANTS
+        .iter()
+        .map(|key| key.as_ref())
+        .collect::<BTreeSet<_>>();
+    let quote_only = [ContextKey::Text, ContextKey::Html];
+    let without_quote = [PlainText, RichText, PlainTextThread, RichTextThread];
+
+    for source in checked {
+        let Ok(template) = tera.get_template(&source.name) else {
+            continue;
+        };
+
+        let mut walker = Walker::default();
+        walker.nodes(&template.ast);
+
+        for variable in walker.used {
+            let line = line_of(&source.content, &variable);
+            if !keys.contains(variable.as_str()) {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    origin: source.origin.clone(),
+                    line,
+                    message: format!("`{variable}` is not a template variable"),
+                });
+            } else if quote_only.iter().any(|key| key.as_ref() == variable)
+                && without_quote
+                    .iter()
+                    .any(|template_type| template_type.as_ref() == source.name)
+            {
+                findings.push(Finding {
+                    severity: Severity::Warning,
+                    origin: source.origin.clone(),
+                    line,
+                    message: format!("`{variable}` is available only with a quote"),
+                });
+            }
+        }
+    }
+
+    findings
+}
+
+// Make a finding from the given Tera error, with the whole chain of the causes and the line
+// number reported by the parser, if any.
+fn error_finding(origin: String, why: &(dyn Error + 'static)) -> Finding {
+    let message = std::iter::successors(Some(why), |&why| why.source())
+        .map(|why| why.to_string().trim().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let line = POSITION
+        .captures(&message)
+        .and_then(|captures| captures[1].parse().ok());
+
+    Finding { severity: Severity::Error, origin, line, message }
+}
+
+// Find the first line where the given variable appears, as the AST has no position.
+fn line_of(content: &str, variable: &str) -> Option<usize> {
+    content
+        .lines()
+        .position(|line| {
+            IDENTIFIER
+                .captures_iter(line)
+                .any(|captures| &captures[1] == variable)
+        })
+        .map(|index| index + 1)
+}
+
+/// Collects the variables used in a template, except the local ones defined by `for`, `set`, or
+/// the macro arguments.
+#[derive(Default)]
+struct Walker {
+    /// The local variables in the current scope.
+    locals: Vec<String>,
+
+    /// The variables used, in the order of appearance.
+    used: Vec<String>,
+}
+
+impl Walker {
+    fn nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::VariableBlock(_, expr) => self.expr(expr),
+                Node::Set(_, set) => {
+                    self.expr(&set.value);
+                    self.locals.push(set.key.clone());
+                }
+                Node::MacroDefinition(_, definition, _) => {
+                    definition.args.values().flatten().for_each(|expr| self.expr(expr));
+                    self.scoped(definition.args.keys().cloned().collect(), &definition.body);
+                }
+                Node::FilterSection(_, section, _) => {
+                    self.call(&section.filter);
+                    self.nodes(&section.body);
+                }
+                Node::Block(_, block, _) => self.nodes(&block.body),
+                Node::Forloop(_, forloop, _) => {
+                    self.expr(&forloop.container);
+                    let locals = [Some(forloop.value.clone()), forloop.key.clone()]
+                        .into_iter()
+                        .flatten()
+                        .chain(["loop".to_string()])
+                        .collect();
+                    self.scoped(locals, &forloop.body);
+                    if let Some(ref body) = forloop.empty_body {
+                        self.nodes(body);
+                    }
+                }
+                Node::If(condition, _) => {
+                    for (_, expr, body) in &condition.conditions {
+                        self.expr(expr);
+                        self.nodes(body);
+                    }
+                    if let Some((_, ref body)) = condition.otherwise {
+                        self.nodes(body);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Walk the given nodes with the given local variables, which are dropped afterwards along
+    // with the ones defined in the nodes.
+    fn scoped(&mut self, locals: Vec<String>, nodes: &[Node]) {
+        let len = self.locals.len();
+        self.locals.extend(locals);
+        self.nodes(nodes);
+        self.locals.truncate(len);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        self.value(&expr.val);
+        expr.filters.iter().for_each(|filter| self.call(filter));
+    }
+
+    fn call(&mut self, call: &FunctionCall) {
+        call.args.values().for_each(|expr| self.expr(expr));
+    }
+
+    fn value(&mut self, value: &ExprVal) {
+        match value {
+            ExprVal::Ident(ident) => self.ident(ident),
+            ExprVal::Math(math) => {
+                self.expr(&math.lhs);
+                self.expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.expr(&logic.lhs);
+                self.expr(&logic.rhs);
+            }
+            ExprVal::In(in_) => {
+                self.expr(&in_.lhs);
+                self.expr(&in_.rhs);
+            }
+            ExprVal::Test(test) => {
+                // Testing whether a variable is defined is fine, whatever it is.
+                if !matches!(test.name.as_str(), "defined" | "undefined") {
+                    self.ident(&test.ident);
+                }
+                test.args.iter().for_each(|expr| self.expr(expr));
+            }
+            ExprVal::MacroCall(call) => call.args.values().for_each(|expr| self.expr(expr)),
+            ExprVal::FunctionCall(call) => self.call(call),
+            ExprVal::Array(items) => items.iter().for_each(|expr| self.expr(expr)),
+            ExprVal::StringConcat(concat) => concat.values.iter().for_each(|v| self.value(v)),
+            _ => {}
+        }
+    }
+
+    // Record the root of the given identifier, e.g. `message` of `message.user_name`, unless it
+    // is a local variable.
+    fn ident(&mut self, ident: &str) {
+        let root = ident.split(['.', '[']).next().unwrap_or(ident);
+        if root != "__tera_context"
+            && !self.locals.iter().any(|local| local == root)
+            && !self.used.iter().any(|used| used == root)
+        {
+            self.used.push(root.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{
+        check,
+        check::{Finding, Severity},
+        TemplateSource,
+    };
+
+    fn check_one(name: &str, content: &str) -> Vec<Finding> {
+        check(&[TemplateSource {
+            name: name.to_string(),
+            origin: name.to_string(),
+            content: content.to_string(),
+        }])
+    }
+
+    #[test]
+    fn test_valid() {
+        let content = "{% set who = user_name %}{{ who }} {% for line in text %}{{ loop.index }}: \
+                       {{ line }}{% endfor %}{% if foo is defined %}{{ url }}{% endif %}";
+        assert!(check_one("quote.txt", content).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_variable() {
+        let findings = check_one("link.txt", "{{ url }}\n{{ channel | upper }}");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[0].line, Some(2));
+        assert_eq!(
+            findings[0].to_string(),
+            "error: link.txt:2: `channel` is not a template variable"
+        );
+    }
+
+    #[test]
+    fn test_quote_only_variable() {
+        let findings = check_one("plain_text", "{{ url }}\n{{ text | join(sep=' ') }}");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(findings[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let findings = check_one("broken.txt", "{{ url }}\n{% if %}");
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[0].line, Some(2));
+    }
+}
@@ -1,5 +1,8 @@
+use strum::EnumProperty as _;
 use strum_macros::{AsRefStr, EnumProperty, VariantArray};
 
+use crate::template::{locale::TIMESTAMP_FORMAT, strftime, Locale};
+
 /// The key of the context that can be used in the template.
 ///
 /// The following `strum` attributes are used to generate the documentation:
@@ -30,6 +33,42 @@ pub enum ContextKey {
     )]
     IsPrivateChannel,
 
+    #[strum(
+        serialize = "channel_topic",
+        props(
+            description = "The topic of the channel.",
+            example = "Company-wide announcements"
+        )
+    )]
+    ChannelTopic,
+
+    #[strum(
+        serialize = "channel_purpose",
+        props(
+            description = "The purpose of the channel.",
+            example = "This channel is for team-wide communication."
+        )
+    )]
+    ChannelPurpose,
+
+    #[strum(
+        serialize = "workspace_name",
+        props(
+            description = "The name of the workspace. Empty with `--export`, or without the `team:read` scope.",
+            example = "XXX"
+        )
+    )]
+    WorkspaceName,
+
+    #[strum(
+        serialize = "workspace_domain",
+        props(
+            description = "The domain of the workspace, i.e. `<domain>.slack.com`.",
+            example = "xxx"
+        )
+    )]
+    WorkspaceDomain,
+
     #[strum(
         serialize = "user_name",
         props(
@@ -39,6 +78,42 @@ pub enum ContextKey {
     )]
     UserName,
 
+    #[strum(
+        serialize = "user_real_name",
+        props(
+            description = "The real name of the user who posted the message.",
+            example = "Jake Doe"
+        )
+    )]
+    UserRealName,
+
+    #[strum(
+        serialize = "user_display_name",
+        props(
+            description = "The display name of the user who posted the message, which might be empty.",
+            example = "jake"
+        )
+    )]
+    UserDisplayName,
+
+    #[strum(
+        serialize = "user_avatar",
+        props(
+            description = "The URL of the 192x192 avatar image of the user who posted the message.",
+            example = "https://avatars.slack-edge.com/..."
+        )
+    )]
+    UserAvatar,
+
+    #[strum(
+        serialize = "user_title",
+        props(
+            description = "The title in the profile of the user who posted the message.",
+            example = "Software Engineer"
+        )
+    )]
+    UserTitle,
+
     #[strum(
         serialize = "url",
         props(
@@ -48,6 +123,105 @@ pub enum ContextKey {
     )]
     Url,
 
+    #[strum(
+        serialize = "ts",
+        props(
+            description = "The raw Slack timestamp of the message, to format with the `date` or `relative` filters.",
+            example = "1724261952.503309"
+        )
+    )]
+    Ts,
+
+    #[strum(
+        serialize = "thread_ts",
+        props(
+            description = "The raw Slack timestamp of the parent message, if the message is in a thread, or empty.",
+            example = "1724261900.000100"
+        )
+    )]
+    ThreadTs,
+
+    #[strum(
+        serialize = "reply_count",
+        props(
+            description = "The number of the replies, if the message is the parent of a thread, or 0.",
+            example = "3"
+        )
+    )]
+    ReplyCount,
+
+    #[strum(
+        serialize = "reactions",
+        props(
+            description = "The reactions to the message, each of which has `name`, e.g. `white_check_mark`, `emoji`, e.g. `✅`, or `:name:` for the custom ones, and `count`.",
+            example = "[...]"
+        )
+    )]
+    Reactions,
+
+    #[strum(
+        serialize = "files",
+        props(
+            description = "The files shared with the message, each of which has `id`, `name`, `mimetype`, `size` in bytes, `permalink`, `thumbnail` for images, `download_url`, and `path` if downloaded with `--download-files`.",
+            example = "[...]"
+        )
+    )]
+    Files,
+
+    #[strum(
+        serialize = "show_reactions",
+        props(
+            description = "Whether to show the line of the reactions and the number of the replies under the quote, i.e. `--reactions` is given.",
+            example = "false"
+        )
+    )]
+    ShowReactions,
+
+    #[strum(
+        serialize = "is_edited",
+        props(
+            description = "Whether the message has been edited or not.",
+            example = "true"
+        )
+    )]
+    IsEdited,
+
+    #[strum(
+        serialize = "edited_ts",
+        props(
+            description = "The raw Slack timestamp of the last edit, or empty if not edited.",
+            example = "1724262012.000000"
+        )
+    )]
+    EditedTs,
+
+    #[strum(
+        serialize = "edited_timestamp",
+        props(
+            format = "%Y-%m-%d %H:%M:%S (%Z)",
+            description = "The timestamp of the last edit, or empty if not edited."
+        )
+    )]
+    EditedTimestamp,
+
+    #[strum(
+        serialize = "user_tz",
+        props(
+            description = "The IANA time zone database identifier in the Slack profile of the user who posted the message, or empty if unknown.",
+            example = "America/Los_Angeles"
+        )
+    )]
+    UserTz,
+
+    #[strum(
+        serialize = "system_tz",
+        props(
+            description = "The IANA time zone database identifier of the system, i.e. `TZ` or `/etc/localtime`, or `UTC` if it cannot be detected.",
+            example = "Asia/Tokyo"
+        )
+    )]
+    SystemTz,
+
     #[strum(
         serialize = "text",
         props(
@@ -66,6 +240,24 @@ pub enum ContextKey {
     )]
     Html,
 
+    #[strum(
+        serialize = "messages",
+        props(
+            description = "The messages in the thread, each of which has `user_name`, `url`, `ts`, `text`, `html`, the metadata of the message and the user, and the date variables.",
+            example = "[...]"
+        )
+    )]
+    Messages,
+
+    #[strum(
+        serialize = "zones",
+        props(
+            description = "The date variables in each of the time zones given with `--timezone`, in the given order, each of which has `zone` and `dates`, e.g. `zones[1].dates.clock`.",
+            example = "[...]"
+        )
+    )]
+    Zones,
+
     #[strum(
         serialize = "timestamp",
         props(
@@ -263,6 +455,40 @@ pub enum ContextKey {
     OffsetColon,
 }
 
+impl ContextKey {
+    /// The description of the key.
+    pub fn description(&self) -> &'static str {
+        self.get_str("description").unwrap_or_default()
+    }
+
+    /// The [`jiff::fmt::strtime`] format specifier of the key, if it is computed from the
+    /// timestamp.
+    pub fn format(&self) -> Option<&'static str> {
+        self.get_str("format")
+    }
+
+    /// The format specifier of the key with the given locale, which differs from
+    /// [`ContextKey::format`] only for `timestamp` and `edited_timestamp`, written in the date and
+    /// time formats of the locale.
+    pub fn localized_format(&self, locale: Option<Locale>) -> Option<&'static str> {
+        match (self, locale) {
+            (ContextKey::Timestamp | ContextKey::EditedTimestamp, Some(_)) => {
+                Some(TIMESTAMP_FORMAT)
+            }
+            _ => self.format(),
+        }
+    }
+
+    /// The example value of the key. The keys computed from the timestamp are formatted for the
+    /// given datetime, in the given locale if any.
+    pub fn example(&self, datetime: &jiff::Zoned, locale: Option<Locale>) -> String {
+        match self.localized_format(locale) {
+            Some(format) => strftime(datetime, format, locale),
+            None => self.get_str("example").unwrap_or("-").to_string(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -317,17 +543,10 @@ mod tests {
             println!(
                 "| {:16} | {:80} | {:36} | {:80} |",
                 format!("`{}`", key.as_ref()),
-                if let Some(format) = key.get_str("format") {
-                    format!("`{format}`")
-                } else {
-                    "(not available)".to_string()
-                },
-                if let Some(format) = key.get_str("format") {
-                    format!("`{}`", datetime.strftime(format))
-                } else {
-                    format!("`{}`", key.get_str("example").unwrap_or("-"))
-                },
-                key.get_str("description").unwrap()
+                key.format()
+                    .map_or("(not available)".to_string(), |format| format!("`{format}`")),
+                format!("`{}`", key.example(&datetime, None)),
+                key.description()
             );
         });
 
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use jiff::{fmt::strtime, tz::TimeZone, Timestamp, Zoned};
+use tera::{Error, Filter, Result, Value};
+
+use crate::{
+    slack::parse_ts,
+    template::{locale::TIMESTAMP_FORMAT, Locale},
+};
+
+/// The format of the `date` filter when no `format` and no locale are given, which is the same as
+/// `timestamp`.
+const DEFAULT_FORMAT: &str = "%Y-%m-%d %H:%M:%S (%Z)";
+
+/// Format a timestamp with any [`jiff::fmt::strtime`] format, e.g.
+/// `{{ ts | date(format="%d %b") }}`, in the configured time zone unless `timezone` is given.
+/// Replaces the built-in `date` filter of Tera.
+pub struct Date {
+    /// The time zone to use when no `timezone` is given.
+    pub timezone: TimeZone,
+
+    /// The locale of the names of the months and the weekdays, and the default format, if any.
+    pub locale: Option<Locale>,
+}
+
+/// Describe a timestamp relative to the current time, e.g. `3 days ago` or `yesterday at 14:02`,
+/// in the configured time zone unless `timezone` is given. It is always in English, regardless of
+/// the locale.
+pub struct Relative {
+    /// The time zone to use when no `timezone` is given.
+    pub timezone: TimeZone,
+}
+
+impl Filter for Date {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+        let datetime = zoned(value, args, &self.timezone)?;
+        let format = match args.get("format") {
+            Some(Value::String(format)) => format.as_str(),
+            Some(_) => return Err(Error::msg("`format` of the `date` filter must be a string")),
+            None if self.locale.is_some() => TIMESTAMP_FORMAT,
+            None => DEFAULT_FORMAT,
+        };
+        let localized = match self.locale {
+            Some(locale) => locale.localize(format, &datetime),
+            None => format.into(),
+        };
+
+        strtime::format(localized.as_ref(), &datetime)
+            .map(Value::String)
+            .map_err(|why| Error::msg(format!("failed to format {value} with {format}: {why}")))
+    }
+}
+
+impl Filter for Relative {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+        let datetime = zoned(value, args, &self.timezone)?;
+        let now = Timestamp::now().to_zoned(datetime.time_zone().clone());
+        relative(&datetime, &now).map(Value::String)
+    }
+}
+
+// Convert the filtered value into a datetime, in the time zone given as `timezone` or the default
+// one. The value is either a Slack timestamp, e.g. `1724743664.325609`, an RFC 3339 timestamp, or
+// the number of seconds since the Unix epoch.
+fn zoned(value: &Value, args: &HashMap<String, Value>, timezone: &TimeZone) -> Result<Zoned> {
+    let timestamp = match value {
+        Value::String(ts) if ts.contains(['T', 't']) => ts.parse::<Timestamp>().ok(),
+        Value::String(ts) => parse_ts(ts)
+            .ok()
+            .and_then(|micros| Timestamp::from_microsecond(micros).ok()),
+        Value::Number(seconds) => seconds
+            .as_i64()
+            .and_then(|seconds| Timestamp::from_second(seconds).ok()),
+        _ => None,
+    }
+    .ok_or_else(|| Error::msg(format!("{value} is not a timestamp")))?;
+
+    let timezone = match args.get("timezone") {
+        Some(Value::String(name)) => TimeZone::get(name)
+            .map_err(|why| Error::msg(format!("unknown time zone {name}: {why}")))?,
+        Some(_) => return Err(Error::msg("`timezone` must be a string")),
+        None => timezone.clone(),
+    };
+
+    Ok(timestamp.to_zoned(timezone))
+}
+
+// Describe the given datetime relative to `now`, by the calendar days in the time zone of `now`.
+fn relative(datetime: &Zoned, now: &Zoned) -> Result<String> {
+    let seconds = (now.timestamp().as_second() - datetime.timestamp().as_second()).max(0);
+    let days = datetime
+        .date()
+        .until(now.date())
+        .map_err(|why| Error::msg(format!("failed to compare the dates: {why}")))?
+        .get_days();
+
+    Ok(match (seconds, days) {
+        (..60, _) => "just now".to_string(),
+        (..3600, _) => ago(seconds / 60, "minute"),
+        (_, ..=0) => ago(seconds / 3600, "hour"),
+        (_, 1) => format!("yesterday at {}", datetime.strftime("%H:%M")),
+        (_, ..7) => ago(days.into(), "day"),
+        (_, ..30) => ago((days / 7).into(), "week"),
+        (_, ..365) => ago((days / 30).into(), "month"),
+        _ => ago((days / 365).into(), "year"),
+    })
+}
+
+// Format the given number of units ago, e.g. `1 minute ago` or `3 days ago`.
+fn ago(count: i64, unit: &str) -> String {
+    format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use jiff::{tz::TimeZone, Timestamp};
+    use tera::{Filter, Value};
+
+    use crate::template::filters::{relative, Date};
+
+    #[test]
+    fn test_date() -> Result<()> {
+        let filter = Date {
+            timezone: TimeZone::get("Asia/Tokyo")?,
+            locale: None,
+        };
+        let ts = Value::from("1724743664.325609");
+
+        #[rustfmt::skip]
+        [
+            (vec![],                                                    "2024-08-27 16:27:44 (JST)"),
+            (vec![("format", "%d %b")],                                 "27 Aug"),
+            (vec![("format", "%H:%M"), ("timezone", "Europe/Berlin")], "09:27"),
+        ]
+        .into_iter()
+        .try_for_each(|(args, expected)| {
+            let args = args.into_iter().map(|(k, v)| (k.to_string(), Value::from(v))).collect();
+            assert_eq!(filter.filter(&ts, &args)?, Value::from(expected));
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        let args = HashMap::from([("format".to_string(), Value::from("%F"))]);
+        assert_eq!(filter.filter(&Value::from(1724743664), &args)?, Value::from("2024-08-27"));
+        assert!(filter.filter(&Value::from("yesterday"), &HashMap::new()).is_err());
+
+        let filter = Date { locale: Some("ja_JP".parse()?), ..filter };
+        assert_eq!(
+            filter.filter(
+                &ts,
+                &HashMap::from([("format".to_string(), Value::from("%-m月%-d日(%a)"))])
+            )?,
+            Value::from("8月27日(火)")
+        );
+        assert_eq!(
+            filter.filter(&ts, &HashMap::new())?,
+            Value::from("2024年08月27日 16時27分44秒 (JST)")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_relative() -> Result<()> {
+        let timezone = TimeZone::get("Asia/Tokyo")?;
+        let now = Timestamp::from_second(1724743664)?.to_zoned(timezone.clone());
+        let before = |seconds: i64| {
+            Timestamp::from_second(1724743664 - seconds).map(|ts| ts.to_zoned(timezone.clone()))
+        };
+
+        #[rustfmt::skip]
+        [
+            (30,             "just now"),
+            (60,             "1 minute ago"),
+            (60 * 59,        "59 minutes ago"),
+            (60 * 60 * 3,    "3 hours ago"),
+            (60 * 60 * 24,   "yesterday at 16:27"),
+            (86400 * 3,      "3 days ago"),
+            (86400 * 14,     "2 weeks ago"),
+            (86400 * 90,     "3 months ago"),
+            (86400 * 800,    "2 years ago"),
+        ]
+        .into_iter()
+        .try_for_each(|(seconds, expected)| {
+            assert_eq!(relative(&before(seconds)?, &now)?, expected);
+            Ok::<_, anyhow::Error>(())
+        })?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use std::{borrow::Cow, str::FromStr};
+
+use anyhow::{anyhow, Error, Result};
+use jiff::Zoned;
+use pure_rust_locales::{locale_match, Locale as Data};
+use serde::Deserialize;
+
+/// The format of the `timestamp` variable and the `date` filter with a locale, i.e. the date and
+/// the time in the formats of the locale, and the time zone abbreviation.
+pub const TIMESTAMP_FORMAT: &str = "%x %X (%Z)";
+
+/// A locale to write the names of the months and the weekdays, and the date and time formats
+/// with, e.g. `ja_JP`. The data is bundled from glibc, so it works offline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Locale(Data);
+
+impl FromStr for Locale {
+    type Err = Error;
+
+    /// Parse the given locale name, e.g. `ja_JP`, `ja-JP`, or `ja_JP.UTF-8`. The encoding is
+    /// ignored.
+    fn from_str(name: &str) -> Result<Self> {
+        let normalized = name.split('.').next().unwrap_or(name).replace('-', "_");
+        Data::try_from(normalized.as_str())
+            .map(Self)
+            .map_err(|_| anyhow!("unknown locale {name}. Use the name like `ja_JP` or `de_DE`"))
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = Error;
+
+    fn try_from(name: String) -> Result<Self> {
+        name.parse()
+    }
+}
+
+impl Locale {
+    /// Rewrite the given [`jiff::fmt::strtime`] format for the given datetime, replacing the
+    /// names of the month (`%B`, `%b`, `%h`), the weekday (`%A`, `%a`), and AM/PM (`%p`, `%P`)
+    /// with the localized ones, and the date and time formats (`%c`, `%x`, `%X`, `%r`) with the
+    /// ones of the locale. The other specifiers are kept as is.
+    pub fn localize<'a>(self, format: &'a str, datetime: &Zoned) -> Cow<'a, str> {
+        if !format.contains('%') {
+            return Cow::Borrowed(format);
+        }
+        Cow::Owned(self.rewrite(format, datetime, 0))
+    }
+
+    // Rewrite the given format, expanding the date and time formats of the locale up to a few
+    // levels, as they might refer to each other, e.g. `%X` to `%r` in `en_US`.
+    fn rewrite(self, format: &str, datetime: &Zoned, depth: usize) -> String {
+        let mut rewritten = String::with_capacity(format.len());
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                rewritten.push(c);
+                continue;
+            }
+
+            // The flags, the padding width, and the `E` and `O` modifiers of glibc, which jiff
+            // does not support and are dropped.
+            let mut flags = String::new();
+            while let Some(&c) = chars.peek() {
+                match c {
+                    '_' | '-' | '0' | '^' | '#' | ':' | '1'..='9' => flags.push(c),
+                    'E' | 'O' => {}
+                    _ => break,
+                }
+                chars.next();
+            }
+            let Some(specifier) = chars.next() else {
+                rewritten.push('%');
+                rewritten.push_str(&flags);
+                break;
+            };
+
+            let name = match specifier {
+                'A' => weekday(datetime, locale_match!(self.0 => LC_TIME::DAY)),
+                'a' => weekday(datetime, locale_match!(self.0 => LC_TIME::ABDAY)),
+                'B' => month(datetime, locale_match!(self.0 => LC_TIME::MON)),
+                'b' | 'h' => month(datetime, locale_match!(self.0 => LC_TIME::ABMON)),
+                'p' => self.am_pm(datetime),
+                'P' => self.am_pm(datetime).to_lowercase(),
+                'c' | 'x' | 'X' | 'r' if depth < 3 => {
+                    let expanded = match specifier {
+                        'c' => locale_match!(self.0 => LC_TIME::D_T_FMT),
+                        'x' => locale_match!(self.0 => LC_TIME::D_FMT),
+                        'X' => locale_match!(self.0 => LC_TIME::T_FMT),
+                        _ => locale_match!(self.0 => LC_TIME::T_FMT_AMPM),
+                    };
+                    // Some locales have no 12-hour clock format.
+                    let expanded = if expanded.is_empty() { "%I:%M:%S %p" } else { expanded };
+                    rewritten.push_str(&self.rewrite(expanded, datetime, depth + 1));
+                    continue;
+                }
+                _ => {
+                    rewritten.push('%');
+                    rewritten.push_str(&flags);
+                    rewritten.push(specifier);
+                    continue;
+                }
+            };
+
+            let name = if flags.contains('^') { name.to_uppercase() } else { name };
+            rewritten.push_str(&name.replace('%', "%%"));
+        }
+
+        rewritten
+    }
+
+    // The name of AM or PM of the given datetime.
+    fn am_pm(self, datetime: &Zoned) -> String {
+        let names = locale_match!(self.0 => LC_TIME::AM_PM);
+        let index = usize::from(datetime.hour() >= 12);
+        names.get(index).copied().unwrap_or_default().to_string()
+    }
+}
+
+// The name of the weekday of the given datetime in the given names, starting from Sunday.
+fn weekday(datetime: &Zoned, names: &[&str]) -> String {
+    let index = datetime.weekday().to_sunday_zero_offset() as usize;
+    names.get(index).copied().unwrap_or_default().to_string()
+}
+
+// The name of the month of the given datetime in the given names. Some locales pad them, e.g.
+// ` 8月` in `ja_JP`, which is trimmed.
+fn month(datetime: &Zoned, names: &[&str]) -> String {
+    let index = datetime.month() as usize - 1;
+    names.get(index).copied().unwrap_or_default().trim().to_string()
+}
+
+/// Format the given datetime with the given [`jiff::fmt::strtime`] format, localized with the
+/// given locale if any.
+pub fn strftime(datetime: &Zoned, format: &str, locale: Option<Locale>) -> String {
+    match locale {
+        Some(locale) => datetime
+            .strftime(locale.localize(format, datetime).as_ref())
+            .to_string(),
+        None => datetime.strftime(format).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::template::{locale::TIMESTAMP_FORMAT, strftime, Locale};
+
+    #[test]
+    fn test_localize() -> Result<()> {
+        let datetime = jiff::Timestamp::from_microsecond(1724743664325609)?.in_tz("Asia/Tokyo")?;
+        let ja = Some("ja_JP".parse::<Locale>()?);
+        let de = Some("de-DE.UTF-8".parse::<Locale>()?);
+
+        #[rustfmt::skip]
+        [
+            ("%-m月%-d日(%a)",       ja,   "8月27日(火)"),
+            ("%A %p%I時",            ja,   "火曜日 午後04時"),
+            (TIMESTAMP_FORMAT,       ja,   "2024年08月27日 16時27分44秒 (JST)"),
+            ("%A, %-d. %B %Y",       de,   "Dienstag, 27. August 2024"),
+            ("%^a %b",               de,   "DI Aug"),
+            ("%%B %B",               de,   "%B August"),
+            ("%A, %B %-d",           None, "Tuesday, August 27"),
+        ]
+        .into_iter()
+        .for_each(|(format, locale, expected)| {
+            assert_eq!(strftime(&datetime, format, locale), expected, "{format}")
+        });
+
+        assert!("xx_YY".parse::<Locale>().is_err());
+
+        Ok(())
+    }
+}
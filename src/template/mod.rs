@@ -1,12 +1,51 @@
+mod check;
 mod context_key;
+mod filters;
+mod locale;
+mod named;
+mod preset;
 mod template_type;
 
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+pub use check::{check, Finding, Severity};
 use clap::Parser;
 pub use context_key::ContextKey;
+use filters::{Date, Relative};
+use jiff::tz::TimeZone;
+pub use locale::{strftime, Locale};
+pub use named::{NamedTemplate, NamedTemplates};
+pub use preset::{escape_markup, Preset};
+use serde::Deserialize;
 pub use template_type::TemplateType;
+use tera::Tera;
+use tokio::fs::read_to_string;
+use TemplateType::*;
+
+const TEMPLATE_RICH_TEXT: &str = include_str!("../../templates/rich_text");
+const TEMPLATE_RICH_TEXT_QUOTE: &str = include_str!("../../templates/rich_text_quote");
+const TEMPLATE_RICH_TEXT_THREAD: &str = include_str!("../../templates/rich_text_thread");
+const TEMPLATE_PLAIN_TEXT_QUOTE_ZONES: &str =
+    include_str!("../../templates/plain_text_quote_zones");
+const TEMPLATE_RICH_TEXT_QUOTE_ZONES: &str = include_str!("../../templates/rich_text_quote_zones");
+const TEMPLATE_ZONES: &str = include_str!("../../templates/zones");
+const TEMPLATE_REACTIONS: &str = include_str!("../../templates/reactions");
+
+/// The source of a template, before being added to the Tera template engine.
+pub struct TemplateSource {
+    /// The name of the template in Tera.
+    pub name: String,
+
+    /// Where the template comes from, i.e. the path to the file, the option, or `built-in`.
+    pub origin: String,
+
+    /// The content of the template.
+    pub content: String,
+}
 
 /// Templates for the client.
-#[derive(Parser, Clone)]
+#[derive(Parser, Clone, Default, Deserialize)]
 pub struct Templates {
     /// Path to the template file or a string for plain text (without quote). Leave empty to use
     /// the default.
@@ -27,4 +66,143 @@ pub struct Templates {
     /// template.
     #[arg(long, env = "TEMPLATE_RICH_TEXT_QUOTE")]
     pub rich_text_quote: Option<String>,
+
+    /// Path to the template file or a string for plain text (with thread). Leave empty to use the
+    /// default.
+    #[arg(long, env = "TEMPLATE_PLAIN_TEXT_THREAD")]
+    pub plain_text_thread: Option<String>,
+
+    /// Path to the template file or a string for rich text (with thread). Leave empty to use the
+    /// default.
+    #[arg(long, env = "TEMPLATE_RICH_TEXT_THREAD")]
+    pub rich_text_thread: Option<String>,
+
+    /// Path to the template file or a string for the header of the file to append the messages
+    /// to, which is written when the file is created. Leave empty to write no header.
+    #[arg(long, env = "TEMPLATE_APPEND_HEADER")]
+    pub append_header: Option<String>,
+}
+
+impl Templates {
+    /// Fill the templates not set in `self` with the `fallback`.
+    pub fn or(self, fallback: Templates) -> Templates {
+        Templates {
+            plain_text: self.plain_text.or(fallback.plain_text),
+            plain_text_quote: self.plain_text_quote.or(fallback.plain_text_quote),
+            rich_text: self.rich_text.or(fallback.rich_text),
+            rich_text_quote: self.rich_text_quote.or(fallback.rich_text_quote),
+            plain_text_thread: self.plain_text_thread.or(fallback.plain_text_thread),
+            rich_text_thread: self.rich_text_thread.or(fallback.rich_text_thread),
+            append_header: self.append_header.or(fallback.append_header),
+        }
+    }
+}
+
+/// Collect the sources of all the templates, i.e. the given [`Templates`], which might contain
+/// paths to the template file or just the template strings, and the ones in the given directory.
+/// The plain text templates not given fall back to the ones of the [`Preset`]. The defaults are
+/// also available as `builtin/<name>` to extend, along with the variants of the quote templates
+/// for `quote_zones`.
+///
+/// # Returns
+///
+/// The sources of the templates, and the named templates.
+#[rustfmt::skip]
+pub async fn load(
+    templates: &Templates,
+    preset: Preset,
+    dir: Option<&Path>,
+) -> Result<(Vec<TemplateSource>, NamedTemplates)> {
+    let [plain_text, plain_text_quote, plain_text_thread] = preset.plain_text_templates();
+    let mut sources = Vec::new();
+
+    for (name, pathlike, default) in [
+        (PlainText,       &templates.plain_text,        plain_text),
+        (PlainTextQuote,  &templates.plain_text_quote,  plain_text_quote),
+        (RichText,        &templates.rich_text,         TEMPLATE_RICH_TEXT),
+        (RichTextQuote,   &templates.rich_text_quote,   TEMPLATE_RICH_TEXT_QUOTE),
+        (PlainTextThread, &templates.plain_text_thread, plain_text_thread),
+        (RichTextThread,  &templates.rich_text_thread,  TEMPLATE_RICH_TEXT_THREAD),
+        (AppendHeader,    &templates.append_header,     ""),
+    ] {
+        sources.push(TemplateSource {
+            name: format!("builtin/{}", name.as_ref()),
+            origin: "built-in".to_string(),
+            content: default.to_string(),
+        });
+        sources.push(source(name, pathlike, default).await?);
+    }
+
+    // The variants of the quote templates with the time in the other time zones, which override
+    // the `timestamp` block of the ones above, and the parts included by them.
+    for (name, content) in [
+        ("plain_text_quote_zones", TEMPLATE_PLAIN_TEXT_QUOTE_ZONES),
+        ("rich_text_quote_zones",  TEMPLATE_RICH_TEXT_QUOTE_ZONES),
+        ("zones",                  TEMPLATE_ZONES),
+        ("reactions",              TEMPLATE_REACTIONS),
+    ] {
+        sources.push(TemplateSource {
+            name: format!("builtin/{name}"),
+            origin: "built-in".to_string(),
+            content: content.to_string(),
+        });
+    }
+
+    let mut named = NamedTemplates::builtin();
+    if let Some(dir) = dir {
+        sources.extend(named.load_dir(dir)?);
+    }
+
+    Ok((sources, named))
+}
+
+/// Set up the Tera template engine with the given sources. They are added at once, so that any
+/// template can extend, include, or import any other. The `date` and `relative` filters use the
+/// given time zone by default, and the `date` filter writes the names in the given locale.
+pub fn setup_tera(
+    sources: &[TemplateSource],
+    timezone: &str,
+    locale: Option<Locale>,
+) -> Result<Tera> {
+    let mut tera = Tera::default();
+    tera.register_filter("escape_markup", escape_markup);
+    // Do not escape the variables in the templates named `*.html`, as `html` holds HTML.
+    tera.autoescape_on(vec![]);
+
+    register_filters(&mut tera, timezone, locale)?;
+    tera.add_raw_templates(sources.iter().map(|source| (&source.name, &source.content)))?;
+
+    Ok(tera)
+}
+
+/// Register the `date` and `relative` filters to the given Tera template engine, replacing the
+/// existing ones, with the given time zone and locale as the defaults.
+pub fn register_filters(tera: &mut Tera, timezone: &str, locale: Option<Locale>) -> Result<()> {
+    let timezone = TimeZone::get(timezone)?;
+    tera.register_filter("date", Date { timezone: timezone.clone(), locale });
+    tera.register_filter("relative", Relative { timezone });
+
+    Ok(())
+}
+
+// Resolve the template source from the given pathlike. If the pathlike is not a valid path, then
+// use it as is assuming it's a template string. If no pathlike is given, then use the default
+// template string.
+async fn source(
+    name: TemplateType,
+    pathlike: &Option<String>,
+    default: &str,
+) -> Result<TemplateSource> {
+    let (origin, content) = match pathlike {
+        Some(pathlike) if PathBuf::from(pathlike).exists() => (
+            pathlike.clone(),
+            read_to_string(pathlike)
+                .await
+                .map_err(|why| anyhow!("failed to read template {pathlike}: {why}"))?,
+        ),
+        Some(pathlike) => (format!("--{}", name.as_ref().replace('_', "-")), pathlike.clone()),
+        None => ("built-in".to_string(), default.to_string()),
+    };
+
+    Ok(TemplateSource { name: name.as_ref().to_string(), origin, content })
 }
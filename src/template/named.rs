@@ -0,0 +1,225 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::template::{TemplateSource, TemplateType::*};
+
+/// A template selectable by the name, which produces the plain text, the rich text, or both.
+#[derive(Debug, Clone, Default)]
+pub struct NamedTemplate {
+    /// The name of the Tera template for the plain text, if it produces one.
+    pub text: Option<String>,
+
+    /// The name of the Tera template for the rich text, i.e. HTML, if it produces one.
+    pub html: Option<String>,
+}
+
+/// The named templates, keyed by the name.
+pub struct NamedTemplates(BTreeMap<String, NamedTemplate>);
+
+impl NamedTemplates {
+    /// The built-in templates, i.e. `link`, `quote`, and `thread`, which use the templates for
+    /// plain text and rich text without a quote, with a quote, and with the whole thread,
+    /// respectively, and `quote_zones`, which extends the ones with a quote to show the time in
+    /// the other time zones in parentheses.
+    #[rustfmt::skip]
+    pub fn builtin() -> Self {
+        Self(
+            [
+                ("link",        PlainText.as_ref(),       RichText.as_ref()),
+                ("quote",       PlainTextQuote.as_ref(),  RichTextQuote.as_ref()),
+                ("thread",      PlainTextThread.as_ref(), RichTextThread.as_ref()),
+                ("quote_zones", "builtin/plain_text_quote_zones", "builtin/rich_text_quote_zones"),
+            ]
+            .into_iter()
+            .map(|(name, text, html)| {
+                let template = NamedTemplate {
+                    text: Some(text.to_string()),
+                    html: Some(html.to_string()),
+                };
+                (name.to_string(), template)
+            })
+            .collect(),
+        )
+    }
+
+    /// Read all the template files in the given directory and its subdirectories, and register
+    /// the ones directly in the directory by the file name without the extension. `<name>.html`
+    /// produces the rich text, and `<name>.txt` or `<name>` produces the plain text. The other
+    /// files, e.g. the base layouts or the macros in the subdirectories, can be used only via
+    /// `extends`, `include`, or `import`. A template in the directory replaces the built-in one
+    /// with the same name as a whole.
+    ///
+    /// # Returns
+    ///
+    /// The sources of all the template files, named by the path relative to the directory
+    /// separated by `/`.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<Vec<TemplateSource>> {
+        let mut files = Vec::new();
+        let mut loaded = BTreeMap::<String, NamedTemplate>::new();
+
+        for path in walk(dir, &mut HashSet::new())? {
+            let name = path
+                .strip_prefix(dir)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let content = read_to_string(&path)
+                .map_err(|why| anyhow!("failed to read {}: {why}", path.display()))?;
+            files.push(TemplateSource {
+                name: name.clone(),
+                origin: path.display().to_string(),
+                content,
+            });
+
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if path.parent() != Some(dir) {
+                continue;
+            }
+
+            let template = loaded.entry(stem.to_string()).or_default();
+            let slot = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("html") => &mut template.html,
+                Some("txt") | None => &mut template.text,
+                Some(_) => continue,
+            };
+            if let Some(existing) = slot.as_ref() {
+                return Err(anyhow!(
+                    "template '{stem}' is defined twice in {}: {existing} and {name}",
+                    dir.display()
+                ));
+            }
+            *slot = Some(name);
+        }
+
+        // A file with an unknown extension might leave an empty entry.
+        loaded.retain(|_, template| template.text.is_some() || template.html.is_some());
+        self.0.extend(loaded);
+
+        Ok(files)
+    }
+
+    /// Get the template with the given name.
+    pub fn get(&self, name: &str) -> Result<NamedTemplate> {
+        self.0.get(name).cloned().ok_or_else(|| {
+            let names = self.0.keys().map(String::as_str).collect::<Vec<_>>().join(", ");
+            anyhow!("template '{name}' is not defined. Available templates are: {names}")
+        })
+    }
+}
+
+// List the files in the given directory and its subdirectories recursively, skipping the hidden
+// ones. A directory already visited, e.g. via a symbolic link to its ancestor, is skipped so that
+// the walk always ends.
+fn walk(dir: &Path, visited: &mut HashSet<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let canonical = dir
+        .canonicalize()
+        .map_err(|why| anyhow!("failed to read templates in {}: {why}", dir.display()))?;
+    if !visited.insert(canonical) {
+        return Ok(files);
+    }
+
+    let entries = read_dir(dir)
+        .map_err(|why| anyhow!("failed to read templates in {}: {why}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'))
+        {
+            continue;
+        }
+
+        if path.is_dir() {
+            files.extend(walk(&path, visited)?);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        process,
+    };
+
+    use anyhow::Result;
+    use tera::Context;
+
+    use crate::template::{setup_tera, NamedTemplates};
+
+    #[test]
+    fn test_named_templates() -> Result<()> {
+        let dir = temp_dir().join(format!("sam-test-templates-{}", process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir)?;
+        for (file, content) in [
+            ("standup.txt", "- {{ user_name }}"),
+            ("standup.html", "<li>{{ user_name }}</li>"),
+            ("link", "<{{ url }}>"),
+            ("card.html", "<b>{{ user_name }}</b>"),
+            ("notes.md", "ignored"),
+            (".hidden", "ignored"),
+        ] {
+            write(dir.join(file), content)?;
+        }
+
+        // The templates in the subdirectories are loaded, but not registered by the name. The
+        // symbolic link to the directory itself is not followed forever.
+        create_dir_all(dir.join("partials"))?;
+        write(dir.join("partials/footer.txt"), "-- {{ user_name }}")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("partials/loop"))?;
+
+        let mut templates = NamedTemplates::builtin();
+        let sources = templates.load_dir(&dir)?;
+        assert!(sources.iter().any(|source| source.name == "partials/footer.txt"));
+        assert!(templates.get("footer").is_err());
+        let tera = setup_tera(&sources, "UTC", None)?;
+
+        let template = templates.get("standup")?;
+        assert_eq!(template.text.as_deref(), Some("standup.txt"));
+        assert_eq!(template.html.as_deref(), Some("standup.html"));
+
+        // The template in the directory replaces the built-in one as a whole.
+        let template = templates.get("link")?;
+        assert_eq!(template.text.as_deref(), Some("link"));
+        assert_eq!(template.html, None);
+
+        let template = templates.get("card")?;
+        assert_eq!(template.text, None);
+        assert_eq!(template.html.as_deref(), Some("card.html"));
+
+        let mut context = Context::new();
+        context.insert("user_name", "jake");
+        assert_eq!(tera.render("standup.txt", &context)?, "- jake");
+
+        assert_eq!(templates.get("quote")?.text.as_deref(), Some("plain_text_quote"));
+        let why = templates.get("notes").unwrap_err().to_string();
+        assert!(why.contains("card, link, quote, quote_zones, standup, thread"), "{why}");
+        assert!(templates.get(".hidden").is_err());
+
+        // The same name with the plain text extension and without it conflicts.
+        write(dir.join("standup"), "- {{ user_name }}")?;
+        assert!(NamedTemplates::builtin().load_dir(&dir).is_err());
+
+        remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use tera::{Error, Result, Value};
+
+/// Include the plain text templates in the given directory, i.e. for plain text without a quote,
+/// with a quote, and with the whole thread, in this order.
+macro_rules! plain_text_templates {
+    ($dir:literal) => {
+        [
+            include_str!(concat!("../../templates/", $dir, "plain_text")),
+            include_str!(concat!("../../templates/", $dir, "plain_text_quote")),
+            include_str!(concat!("../../templates/", $dir, "plain_text_thread")),
+        ]
+    };
+}
+
+/// A set of the built-in plain text templates for a markup language. The rich text templates are
+/// not affected, as the rich text is always HTML.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Markdown, the default.
+    #[default]
+    Markdown,
+
+    /// Org-mode.
+    Org,
+
+    /// AsciiDoc.
+    #[value(name = "asciidoc")]
+    AsciiDoc,
+
+    /// reStructuredText.
+    Rst,
+
+    /// Jira wiki markup, which Confluence also accepts.
+    Jira,
+
+    /// MediaWiki markup.
+    #[value(name = "mediawiki")]
+    MediaWiki,
+
+    /// BBCode.
+    #[value(name = "bbcode")]
+    BbCode,
+
+    /// Typst.
+    Typst,
+}
+
+impl Preset {
+    /// The plain text templates of the preset.
+    ///
+    /// # Returns
+    ///
+    /// The templates for plain text without a quote, with a quote, and with the whole thread.
+    pub fn plain_text_templates(self) -> [&'static str; 3] {
+        match self {
+            Preset::Markdown => plain_text_templates!(""),
+            Preset::Org => plain_text_templates!("presets/org/"),
+            Preset::AsciiDoc => plain_text_templates!("presets/asciidoc/"),
+            Preset::Rst => plain_text_templates!("presets/rst/"),
+            Preset::Jira => plain_text_templates!("presets/jira/"),
+            Preset::MediaWiki => plain_text_templates!("presets/mediawiki/"),
+            Preset::BbCode => plain_text_templates!("presets/bbcode/"),
+            Preset::Typst => plain_text_templates!("presets/typst/"),
+        }
+    }
+}
+
+/// Escape a line of the Markdown body, or a name, for the markup language of the given preset, e.g.
+/// `{{ line | escape_markup(markup="typst") }}`, so that the characters special to the language,
+/// such as `#` or `]` in Typst, are shown as is instead of breaking the markup around it. The
+/// Markdown syntax in the line is not converted.
+pub fn escape_markup(value: &Value, args: &HashMap<String, Value>) -> Result<Value> {
+    let line = value
+        .as_str()
+        .ok_or_else(|| Error::msg(format!("{value} is not a string")))?;
+    let preset = match args.get("markup") {
+        Some(Value::String(name)) => Preset::from_str(name, true)
+            .map_err(|_| Error::msg(format!("unknown markup language {name}")))?,
+        Some(_) => return Err(Error::msg("`markup` must be a string")),
+        None => return Err(Error::msg("`markup` is required")),
+    };
+
+    Ok(Value::String(escape(line, preset)))
+}
+
+// Escape the given line for the markup language of the given preset. Where the language has no
+// escape character, the tags or the lines which would start a block, e.g. a heading, are broken
+// with an invisible character instead.
+fn escape(line: &str, preset: Preset) -> String {
+    let backslash = |special: &str| {
+        let mut escaped = String::with_capacity(line.len());
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            // A slash is special in Typst only as the start of a comment.
+            let comment = c == '/' && chars.peek().is_some_and(|next| matches!(next, '/' | '*'));
+            if special.contains(c) || comment {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    };
+
+    match preset {
+        Preset::Markdown => line.to_string(),
+        Preset::Typst if line.starts_with('=') => format!("\\{}", backslash(r"\#$@*_[]<>`~")),
+        Preset::Typst => backslash(r"\#$@*_[]<>`~"),
+        Preset::Rst => backslash(r"\*`_|"),
+        Preset::Jira => backslash("{}[]|!*_+^~"),
+        Preset::MediaWiki if line.is_empty() => String::new(),
+        Preset::MediaWiki => {
+            format!("<nowiki>{}</nowiki>", line.replace('&', "&amp;").replace('<', "&lt;"))
+        }
+        Preset::BbCode => line.replace('[', "[\u{200B}"),
+        Preset::Org if line.starts_with(['*', '#']) => format!("\u{200B}{line}"),
+        Preset::AsciiDoc
+            if line.starts_with(['=', '/', '[', '|', '_', '.', '+'])
+                || (line.len() >= 2 && line.chars().all(|c| matches!(c, '-' | '*'))) =>
+        {
+            format!("{{empty}}{line}")
+        }
+        Preset::Org | Preset::AsciiDoc => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use clap::ValueEnum;
+    use serde_json::json;
+    use tera::{Context, Tera, Value};
+
+    use crate::template::{preset::escape_markup, Preset, TEMPLATE_REACTIONS};
+
+    // Render the given template, which might include the reaction line.
+    fn render(template: &str, context: &Context) -> Result<String> {
+        let mut tera = Tera::default();
+        tera.register_filter("escape_markup", escape_markup);
+        tera.add_raw_templates([
+            ("builtin/reactions", TEMPLATE_REACTIONS),
+            ("template", template),
+        ])?;
+        Ok(tera.render("template", context)?)
+    }
+
+    #[test]
+    fn test_render_all_presets() -> Result<()> {
+        let message = json!({
+            "channel_name": "general",
+            "is_private_channel": true,
+            "user_name": "jake [/b]",
+            "url": "https://xxx.slack.com/archives/C01234567/p1724743664325609",
+            "timestamp": "2024-08-27 16:27:44 (JST)",
+            "text": ["Hello,", "", "world! [/quote]"],
+            "files": [{
+                "name": "spec.pdf",
+                "mimetype": "application/pdf",
+                "size": 2048,
+                "permalink": "https://xxx.slack.com/files/U01234567/F01234567/spec.pdf",
+                "path": "",
+            }],
+            "reactions": [{ "name": "eyes", "emoji": "👀", "count": 2 }],
+            "show_reactions": true,
+        });
+        let mut context = Context::from_value(message.clone())?;
+        context.insert("messages", &[message]);
+
+        for preset in Preset::value_variants() {
+            for template in preset.plain_text_templates() {
+                let rendered = render(template, &context)?;
+                assert!(rendered.contains("general"), "{preset:?}: {rendered}");
+                assert!(rendered.contains("🔒"), "{preset:?}: {rendered}");
+                // The tags in the user name and the body do not close the ones around them.
+                assert!(rendered.matches("[/quote]").count() <= 1, "{preset:?}: {rendered}");
+                assert!(rendered.matches("[/b]").count() <= 1, "{preset:?}: {rendered}");
+            }
+
+            // The quote shows the files and the reactions only if any.
+            let quote = preset.plain_text_templates()[1];
+            let rendered = render(quote, &context)?;
+            assert!(rendered.contains("spec.pdf"), "{preset:?}: {rendered}");
+            assert!(rendered.contains("👀 ×2"), "{preset:?}: {rendered}");
+
+            let mut context = context.clone();
+            context.remove("files");
+            context.remove("reactions");
+            let rendered = render(quote, &context)?;
+            assert!(!rendered.contains("spec.pdf"), "{preset:?}: {rendered}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape_markup() -> Result<()> {
+        let escape = |line: &str, preset: Preset| {
+            let args = HashMap::from([("markup".to_string(), Value::from(format!("{preset:?}")))]);
+            escape_markup(&Value::from(line), &args)
+        };
+
+        #[rustfmt::skip]
+        let cases = [
+            ("**Ship** it in #general", Preset::Markdown,  "**Ship** it in #general"),
+            ("**Ship** it in #general", Preset::Typst,     r"\*\*Ship\*\* it in \#general"),
+            ("= [$5] @jake // `x`",     Preset::Typst,     r"\= \[\$5\] \@jake \// \`x\`"),
+            ("https://example.com",     Preset::Typst,     r"https:\//example.com"),
+            ("*a* _b_ `c` |d|",         Preset::Rst,       r"\*a\* \_b\_ \`c\` \|d\|"),
+            ("{quote} [x|y] *a*",       Preset::Jira,      r"\{quote\} \[x\|y\] \*a\*"),
+            ("<b>a & b</b>",            Preset::MediaWiki, "<nowiki>&lt;b>a &amp; b&lt;/b></nowiki>"),
+            ("",                        Preset::MediaWiki, ""),
+            ("* not a heading",         Preset::Org,       "\u{200B}* not a heading"),
+            ("#+end_quote",             Preset::Org,       "\u{200B}#+end_quote"),
+            ("- a list item",           Preset::Org,       "- a list item"),
+            ("____",                    Preset::AsciiDoc,  "{empty}____"),
+            ("- a list item",           Preset::AsciiDoc,  "- a list item"),
+            ("[/quote] [b]x",           Preset::BbCode,    "[\u{200B}/quote] [\u{200B}b]x"),
+        ];
+
+        for (line, preset, expected) in cases {
+            assert_eq!(escape(line, preset)?, Value::from(expected), "{preset:?}: {line}");
+        }
+
+        assert!(escape_markup(&Value::from("x"), &HashMap::new()).is_err());
+        let args = HashMap::from([("markup".to_string(), Value::from("latex"))]);
+        assert!(escape_markup(&Value::from("x"), &args).is_err());
+
+        Ok(())
+    }
+}
@@ -18,4 +18,16 @@ pub enum TemplateType {
     /// For rich text, with a quote.
     #[strum(serialize = "rich_text_quote")]
     RichTextQuote,
+
+    /// For plain text, with the whole thread.
+    #[strum(serialize = "plain_text_thread")]
+    PlainTextThread,
+
+    /// For rich text, with the whole thread.
+    #[strum(serialize = "rich_text_thread")]
+    RichTextThread,
+
+    /// For the header of the file to append the messages to, when it is created.
+    #[strum(serialize = "append_header")]
+    AppendHeader,
 }
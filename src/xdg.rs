@@ -0,0 +1,23 @@
+use std::{env::var_os, path::PathBuf};
+
+/// The directory for the cache files, i.e. `$XDG_CACHE_HOME/sam`, or `~/.cache/sam` if the
+/// variable is not set.
+pub fn cache_dir() -> Option<PathBuf> {
+    base_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// The directory for the configuration files, i.e. `$XDG_CONFIG_HOME/sam`, or `~/.config/sam` if
+/// the variable is not set.
+pub fn config_dir() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", ".config")
+}
+
+// Resolve the base directory from the given XDG environment variable, falling back to the given
+// directory under the home directory, then append the name of this program.
+fn base_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .map(|dir| dir.join(env!("CARGO_BIN_NAME")))
+}